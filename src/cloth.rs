@@ -0,0 +1,172 @@
+use crate::constraint::{DistanceConstraint, LinkKind};
+use crate::verlet_object::VerletObject;
+use crate::world::World;
+use cgmath::Vector2 as Vec2;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ClothPin {
+    None,
+    TopCorners,
+    TopEdge,
+}
+
+/// A rectangular lattice of particles held together by structural, shear and
+/// bend links. Spawned into a [`World`] with [`Cloth::spawn`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Cloth {
+    /// Top-left particle position.
+    pub x: f32,
+    pub y: f32,
+    pub columns: usize,
+    pub rows: usize,
+    /// Rest distance between neighbouring particles.
+    pub spacing: f32,
+    /// Particle radius; half the spacing keeps snow from slipping through.
+    pub radius: f32,
+    pub pin: ClothPin,
+    /// Links tear once stretched past this multiple of their rest length.
+    pub tear_ratio: Option<f32>,
+    pub shear_stiffness: f32,
+    pub bend_stiffness: f32,
+}
+
+impl Default for Cloth {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            columns: 30,
+            rows: 20,
+            spacing: 10.0,
+            radius: 5.0,
+            pin: ClothPin::TopCorners,
+            tear_ratio: Some(3.0),
+            shear_stiffness: 0.5,
+            bend_stiffness: 0.2,
+        }
+    }
+}
+
+impl Cloth {
+    /// Adds the cloth's particles and links to `world`. Returns the particle
+    /// indices in row-major order.
+    pub fn spawn(&self, world: &mut World) -> Vec<usize> {
+        let mut indices = Vec::with_capacity(self.columns * self.rows);
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let pos = Vec2::new(
+                    self.x + column as f32 * self.spacing,
+                    self.y + row as f32 * self.spacing,
+                );
+                let pinned = row == 0
+                    && match self.pin {
+                        ClothPin::None => false,
+                        ClothPin::TopCorners => column == 0 || column + 1 == self.columns,
+                        ClothPin::TopEdge => true,
+                    };
+                indices.push(world.add_particle(VerletObject::new(
+                    pos,
+                    pos,
+                    Vec2::new(0.0, 0.0),
+                    self.radius,
+                    (255, 255, 255),
+                    pinned,
+                )));
+            }
+        }
+
+        let at = |column: usize, row: usize| indices[row * self.columns + column];
+        let diagonal = self.spacing * std::f32::consts::SQRT_2;
+        for row in 0..self.rows {
+            for column in 0..self.columns {
+                let here = at(column, row);
+                let right = column + 1 < self.columns;
+                let down = row + 1 < self.rows;
+
+                // Structural
+                if right {
+                    self.link(world, here, at(column + 1, row), self.spacing, 1.0);
+                }
+                if down {
+                    self.link(world, here, at(column, row + 1), self.spacing, 1.0);
+                }
+                // Shear
+                if right && down {
+                    let stiffness = self.shear_stiffness;
+                    self.link(world, here, at(column + 1, row + 1), diagonal, stiffness);
+                    self.link(
+                        world,
+                        at(column + 1, row),
+                        at(column, row + 1),
+                        diagonal,
+                        stiffness,
+                    );
+                }
+                // Bend
+                if column + 2 < self.columns {
+                    let stiffness = self.bend_stiffness;
+                    self.link(
+                        world,
+                        here,
+                        at(column + 2, row),
+                        self.spacing * 2.0,
+                        stiffness,
+                    );
+                }
+                if row + 2 < self.rows {
+                    let stiffness = self.bend_stiffness;
+                    self.link(
+                        world,
+                        here,
+                        at(column, row + 2),
+                        self.spacing * 2.0,
+                        stiffness,
+                    );
+                }
+            }
+        }
+        indices
+    }
+
+    fn link(&self, world: &mut World, a: usize, b: usize, rest_length: f32, stiffness: f32) {
+        let mut constraint = DistanceConstraint::new(a, b, rest_length, LinkKind::Stick);
+        constraint.stiffness = stiffness;
+        constraint.tear_ratio = self.tear_ratio;
+        world.constraints.push(constraint);
+    }
+
+    /// Problems with this cloth's settings, for config validation.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.columns == 0 || self.rows == 0 {
+            problems.push(format!(
+                "cloth must have at least one row and column, got {}x{}",
+                self.columns, self.rows
+            ));
+        }
+        if self.spacing <= 0.0 {
+            problems.push(format!(
+                "cloth spacing must be positive, got {}",
+                self.spacing
+            ));
+        }
+        if self.radius <= 0.0 {
+            problems.push(format!(
+                "cloth radius must be positive, got {}",
+                self.radius
+            ));
+        }
+        if let Some(ratio) = self.tear_ratio {
+            if ratio <= 1.0 {
+                problems.push(format!(
+                    "cloth tear_ratio must be greater than 1, got {}",
+                    ratio
+                ));
+            }
+        }
+        problems
+    }
+}
@@ -0,0 +1,353 @@
+use crate::cloth::Cloth;
+use crate::container::{Boundary, Container};
+use crate::emitter::{Emitter, EmitterShape};
+use crate::input::InputSettings;
+use crate::material::Material;
+use crate::obstacle::Obstacle;
+use crate::soft_body::SoftBody;
+use crate::stability::{ExplosionGuard, SubstepRange};
+use crate::verlet_object::Solver;
+use crate::world::World;
+use cgmath::Vector2 as Vec2;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Scene and simulation settings, loadable from a TOML file. Every field is
+/// optional in the file and falls back to the same default as the CLI flag.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub particle_size: i32,
+    pub motion_dampening: i32,
+    pub total: i32,
+    pub substeps: i32,
+    /// `{ min = 2, max = 32 }` picks the substep count every frame from how
+    /// fast particles move, starting from `substeps`.
+    pub adaptive_substeps: Option<SubstepRange>,
+    pub gravity: i32,
+    pub cohesion: f32,
+    pub repulsion: f32,
+    /// Coulomb friction coefficients for particle contacts.
+    pub static_friction: f32,
+    pub kinetic_friction: f32,
+    /// Solve collisions on every core; see [`Solver::parallel_collisions`].
+    pub parallel_collisions: bool,
+    /// Fastest a particle may move, in units per second.
+    pub max_speed: Option<f32>,
+    /// `{ threshold = 10, min_speed = 1000, recovery = "damp" }` detects
+    /// updates that blow up and recovers by `"damp"`, `"rollback"` or
+    /// `"substeps"`.
+    pub explosion_guard: Option<ExplosionGuard>,
+    pub variance: i32,
+    /// Derive each particle's mass from its radius with this density.
+    /// Without it every particle has a mass of 1.
+    pub density: Option<f32>,
+    pub seed: Option<u64>,
+    pub frames: u32,
+    pub width: i32,
+    pub height: i32,
+    /// `"closed"` keeps particles in the container, `"open"` lets them leave
+    /// and `"periodic"` wraps them around the window's edges.
+    pub boundary: Boundary,
+    /// Defaults to the window; `[container.circle]` or `[container.ellipse]`
+    /// makes a globe.
+    pub container: Container,
+    /// `[[obstacles]]` tables, each holding one `segment`, `capsule` or
+    /// `polygon`.
+    pub obstacles: Vec<Obstacle>,
+    /// `[[cloths]]` tables, spawned after the starting grid.
+    pub cloths: Vec<Cloth>,
+    /// `[[soft_bodies]]` tables, spawned after the cloths.
+    pub soft_bodies: Vec<SoftBody>,
+    /// `[[emitters]]` tables, each a continuous source of particles.
+    pub emitters: Vec<Emitter>,
+    /// `[[materials]]` tables. Spawned particles pick one by `share`; the CLI
+    /// material flags tune the first.
+    pub materials: Vec<Material>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Parse(toml::de::Error),
+    /// One message per offending field.
+    Invalid(Vec<String>),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "{}", e),
+            ConfigError::Parse(e) => write!(f, "{}", e),
+            ConfigError::Invalid(problems) => write!(f, "{}", problems.join("\n")),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+impl From<toml::de::Error> for ConfigError {
+    fn from(e: toml::de::Error) -> Self {
+        ConfigError::Parse(e)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            particle_size: 10,
+            motion_dampening: 10,
+            total: 1000,
+            substeps: 8,
+            adaptive_substeps: None,
+            gravity: 1000,
+            cohesion: 0.0,
+            repulsion: 0.0,
+            static_friction: 0.0,
+            kinetic_friction: 0.0,
+            parallel_collisions: true,
+            max_speed: None,
+            explosion_guard: None,
+            variance: 0,
+            density: None,
+            seed: None,
+            frames: 600,
+            width: 800,
+            height: 800,
+            boundary: Boundary::Closed,
+            container: Container::Window,
+            obstacles: Vec::new(),
+            cloths: Vec::new(),
+            soft_bodies: Vec::new(),
+            emitters: Vec::new(),
+            materials: vec![Material::default()],
+        }
+    }
+}
+
+impl Config {
+    pub fn from_toml_str(s: &str) -> Result<Self, ConfigError> {
+        Ok(toml::from_str(s)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        Self::from_toml_str(&fs::read_to_string(path)?)
+    }
+
+    /// Checks for values the simulation cannot run with, reporting all of them.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut problems = Vec::new();
+
+        if self.particle_size <= 0 {
+            problems.push(format!(
+                "particle_size must be positive, got {}",
+                self.particle_size
+            ));
+        }
+        if self.motion_dampening == 0 {
+            problems.push("motion_dampening must not be zero".to_string());
+        }
+        if self.total < 0 {
+            problems.push(format!("total must not be negative, got {}", self.total));
+        }
+        if self.substeps <= 0 {
+            problems.push(format!(
+                "substeps must be at least 1, got {}",
+                self.substeps
+            ));
+        }
+        if let Some(range) = self.adaptive_substeps {
+            if range.min < 1 || range.min > range.max {
+                problems.push(format!(
+                    "adaptive_substeps needs 1 <= min <= max, got {}..{}",
+                    range.min, range.max
+                ));
+            }
+        }
+        if self.variance < 0 {
+            problems.push(format!(
+                "variance must not be negative, got {}",
+                self.variance
+            ));
+        }
+        // A larger spread could spawn particles of zero or negative radius
+        if self.variance > 0 && self.particle_size > 0 && self.variance >= self.particle_size {
+            problems.push(format!(
+                "variance ({}) must be smaller than particle_size ({})",
+                self.variance, self.particle_size
+            ));
+        }
+        if let Some(density) = self.density {
+            if !(density > 0.0 && density.is_finite()) {
+                problems.push(format!("density must be positive, got {}", density));
+            }
+        }
+        if !self.cohesion.is_finite() {
+            problems.push(format!("cohesion must be finite, got {}", self.cohesion));
+        }
+        if !self.repulsion.is_finite() {
+            problems.push(format!("repulsion must be finite, got {}", self.repulsion));
+        }
+        for (field, value) in [
+            ("static_friction", self.static_friction),
+            ("kinetic_friction", self.kinetic_friction),
+        ] {
+            if !(value >= 0.0 && value.is_finite()) {
+                problems.push(format!("{} must not be negative, got {}", field, value));
+            }
+        }
+        if self.kinetic_friction > self.static_friction {
+            problems.push(format!(
+                "kinetic_friction ({}) must not exceed static_friction ({})",
+                self.kinetic_friction, self.static_friction
+            ));
+        }
+        if let Some(max_speed) = self.max_speed {
+            if !(max_speed > 0.0 && max_speed.is_finite()) {
+                problems.push(format!("max_speed must be positive, got {}", max_speed));
+            }
+        }
+        if let Some(guard) = self.explosion_guard {
+            if !(guard.threshold > 1.0 && guard.threshold.is_finite()) {
+                problems.push(format!(
+                    "explosion_guard threshold must be above 1, got {}",
+                    guard.threshold
+                ));
+            }
+            if !(guard.min_speed >= 0.0 && guard.min_speed.is_finite()) {
+                problems.push(format!(
+                    "explosion_guard min_speed must not be negative, got {}",
+                    guard.min_speed
+                ));
+            }
+        }
+        if self.width <= 0 || self.height <= 0 {
+            problems.push(format!(
+                "window size must be positive, got {}x{}",
+                self.width, self.height
+            ));
+        }
+        // Wrapping only makes sense across the window's straight edges
+        if self.boundary == Boundary::Periodic && self.container != Container::Window {
+            problems.push("boundary \"periodic\" needs the window container".to_string());
+        }
+        match self.container {
+            Container::Window => {}
+            Container::Circle { radius, .. } => {
+                if radius <= 0.0 {
+                    problems.push(format!("container radius must be positive, got {}", radius));
+                }
+            }
+            Container::Ellipse {
+                radius_x, radius_y, ..
+            } => {
+                if radius_x <= 0.0 || radius_y <= 0.0 {
+                    problems.push(format!(
+                        "container radii must be positive, got {}x{}",
+                        radius_x, radius_y
+                    ));
+                }
+            }
+        }
+        for obstacle in self.obstacles.iter() {
+            problems.extend(obstacle.problems());
+        }
+        for cloth in self.cloths.iter() {
+            problems.extend(cloth.problems());
+        }
+        for body in self.soft_bodies.iter() {
+            problems.extend(body.problems());
+        }
+        for emitter in self.emitters.iter() {
+            problems.extend(emitter.problems());
+        }
+        if self.materials.is_empty() {
+            problems.push("at least one material is required".to_string());
+        }
+        for material in self.materials.iter() {
+            problems.extend(material.problems());
+        }
+
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ConfigError::Invalid(problems))
+        }
+    }
+
+    pub fn solver(&self) -> Solver {
+        let mut solver = Solver::new(
+            Vec2::new(0.0, self.gravity as f32),
+            self.width,
+            self.height,
+            self.substeps,
+            self.cohesion,
+            self.repulsion,
+        );
+        solver.adaptive_substeps = self.adaptive_substeps;
+        solver.boundary = self.boundary;
+        solver.container = self.container;
+        solver.obstacles = self.obstacles.clone();
+        solver.materials = self.materials.clone();
+        solver.static_friction = self.static_friction;
+        solver.kinetic_friction = self.kinetic_friction;
+        solver.parallel_collisions = self.parallel_collisions;
+        solver.max_speed = self.max_speed;
+        solver.explosion_guard = self.explosion_guard;
+        solver
+    }
+
+    /// A world with the starting grid of particles.
+    pub fn build_world<R: Rng>(&self, rng: &mut R) -> World {
+        let mut world = World::new(self.solver());
+        world.density = self.density;
+        world.spawn_grid(self.total, self.particle_size as f32, self.variance, rng);
+        for cloth in self.cloths.iter() {
+            cloth.spawn(&mut world);
+        }
+        for body in self.soft_bodies.iter() {
+            body.spawn(&mut world);
+        }
+        for emitter in self.emitters.iter() {
+            world
+                .emitters
+                .push(emitter.with_seed(emitter.seed ^ rng.random::<u64>()));
+        }
+        world
+    }
+
+    pub fn input_settings(&self) -> InputSettings {
+        let mut emitter = Emitter::default();
+        emitter.shape = EmitterShape::Segment {
+            a: (-50.0, 0.0),
+            b: (50.0, 0.0),
+        };
+        emitter.min_radius = self.particle_size as f32 / 2.0;
+        emitter.max_radius = self.particle_size as f32;
+        InputSettings {
+            particle_size: self.particle_size as f32,
+            size_variance: self.variance,
+            movement_dampening: self.motion_dampening as f32,
+            cloth: Cloth {
+                spacing: self.particle_size as f32,
+                radius: self.particle_size as f32 / 2.0,
+                ..Cloth::default()
+            },
+            soft_body: SoftBody {
+                radius: self.particle_size as f32 * 5.0,
+                ..SoftBody::default()
+            },
+            emitter,
+        }
+    }
+}
@@ -0,0 +1,170 @@
+use crate::verlet_object::{mass_ratios, VerletObject};
+use cgmath::{InnerSpace, Vector2 as Vec2};
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LinkKind {
+    /// Holds the pair at exactly the rest length.
+    Stick,
+    /// Only stops the pair from moving further apart than the rest length.
+    Rope,
+}
+
+/// Keeps particles `a` and `b` at `rest_length` apart.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct DistanceConstraint {
+    pub a: usize,
+    pub b: usize,
+    pub rest_length: f32,
+    /// Fraction of the error corrected per substep, in `0.0..=1.0`.
+    pub stiffness: f32,
+    pub kind: LinkKind,
+    /// Breaks the link once it stretches past `rest_length` times this.
+    pub tear_ratio: Option<f32>,
+}
+
+/// Mutable references to two distinct particles.
+pub(crate) fn pair_mut(
+    particles: &mut [VerletObject],
+    a: usize,
+    b: usize,
+) -> (&mut VerletObject, &mut VerletObject) {
+    if a < b {
+        let (left, right) = particles.split_at_mut(b);
+        (&mut left[a], &mut right[0])
+    } else {
+        let (left, right) = particles.split_at_mut(a);
+        (&mut right[0], &mut left[b])
+    }
+}
+
+/// Drops the links attached to particle `index` and re-points those attached
+/// to `last`, which [`World::remove_particle`] moves into its slot.
+///
+/// [`World::remove_particle`]: crate::world::World::remove_particle
+pub(crate) fn unlink_removed(constraints: &mut Vec<DistanceConstraint>, index: usize, last: usize) {
+    constraints.retain(|c| c.a != index && c.b != index);
+    for c in constraints.iter_mut() {
+        if c.a == last {
+            c.a = index;
+        }
+        if c.b == last {
+            c.b = index;
+        }
+    }
+}
+
+impl DistanceConstraint {
+    pub fn new(a: usize, b: usize, rest_length: f32, kind: LinkKind) -> Self {
+        Self {
+            a,
+            b,
+            rest_length,
+            stiffness: 1.0,
+            kind,
+            tear_ratio: None,
+        }
+    }
+
+    /// Corrects the pair towards the rest length. Returns `false` if the link
+    /// tore instead and should be removed.
+    pub fn solve(&self, particles: &mut [VerletObject]) -> bool {
+        if self.a == self.b {
+            return true;
+        }
+        let (pa, pb) = pair_mut(particles, self.a, self.b);
+
+        let axis = pa.position_current - pb.position_current;
+        let dist = axis.magnitude();
+        if let Some(ratio) = self.tear_ratio {
+            if dist > self.rest_length * ratio {
+                return false;
+            }
+        }
+        if dist == 0.0 || (self.kind == LinkKind::Rope && dist <= self.rest_length) {
+            return true;
+        }
+
+        // A pinned end takes none of the correction, the free end all of it
+        let (wa, wb) = match mass_ratios(pa, pb) {
+            Some(ratios) => ratios,
+            None => return true,
+        };
+
+        let n = axis / dist;
+        let delta = (self.rest_length - dist) * self.stiffness;
+        pa.position_current += n * delta * wa;
+        pb.position_current -= n * delta * wb;
+        true
+    }
+}
+
+/// Keeps the polygon through `indices` near `rest_area * pressure`, so a
+/// closed ring of particles resists being squashed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AreaConstraint {
+    pub indices: Vec<usize>,
+    /// Signed shoelace area at creation.
+    pub rest_area: f32,
+    /// Target area as a multiple of the rest area. Above 1 inflates the body.
+    pub pressure: f32,
+    /// Fraction of the error corrected per substep, in `0.0..=1.0`.
+    pub stiffness: f32,
+}
+
+impl AreaConstraint {
+    /// Captures the current area of the polygon through `indices`.
+    pub fn new(particles: &[VerletObject], indices: Vec<usize>) -> Self {
+        let rest_area = signed_area(particles, &indices);
+        Self {
+            indices,
+            rest_area,
+            pressure: 1.0,
+            stiffness: 1.0,
+        }
+    }
+
+    pub fn solve(&self, particles: &mut [VerletObject]) {
+        let n = self.indices.len();
+        if n < 3 {
+            return;
+        }
+
+        let error = signed_area(particles, &self.indices) - self.rest_area * self.pressure;
+
+        // Gradient of the shoelace area with respect to each vertex
+        let gradients: Vec<Vec2<f32>> = (0..n)
+            .map(|i| {
+                let prev = particles[self.indices[(i + n - 1) % n]].position_current;
+                let next = particles[self.indices[(i + 1) % n]].position_current;
+                Vec2::new(next.y - prev.y, prev.x - next.x) * 0.5
+            })
+            .collect();
+
+        let weights: Vec<f32> = self
+            .indices
+            .iter()
+            .map(|&i| particles[i].inverse_mass())
+            .collect();
+        let denominator: f32 = (0..n).map(|i| weights[i] * gradients[i].magnitude2()).sum();
+        if denominator == 0.0 {
+            return;
+        }
+
+        let lambda = -error / denominator * self.stiffness;
+        for i in 0..n {
+            particles[self.indices[i]].position_current += gradients[i] * lambda * weights[i];
+        }
+    }
+}
+
+fn signed_area(particles: &[VerletObject], indices: &[usize]) -> f32 {
+    let n = indices.len();
+    let mut area = 0.0;
+    for i in 0..n {
+        let a = particles[indices[i]].position_current;
+        let b = particles[indices[(i + 1) % n]].position_current;
+        area += a.x * b.y - b.x * a.y;
+    }
+    area * 0.5
+}
@@ -0,0 +1,79 @@
+use cgmath::Vector2 as Vec2;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+/// Boundary that keeps particles inside the world.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Container {
+    /// The `width` x `height` rectangle from the origin.
+    #[default]
+    Window,
+    Circle {
+        x: f32,
+        y: f32,
+        radius: f32,
+    },
+    Ellipse {
+        x: f32,
+        y: f32,
+        radius_x: f32,
+        radius_y: f32,
+    },
+}
+
+/// What happens to particles at the edge of the world.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Boundary {
+    /// The container keeps particles in.
+    #[default]
+    Closed,
+    /// No walls; particles fly off in any direction and keep colliding
+    /// wherever they end up.
+    Open,
+    /// The `width` x `height` rectangle wraps around: particles leaving one
+    /// side come back in on the opposite one, and collide across the seam.
+    Periodic,
+}
+
+impl Container {
+    /// Center and radii of a round container.
+    pub(crate) fn ellipse(&self) -> Option<(Vec2<f32>, Vec2<f32>)> {
+        match *self {
+            Container::Window => None,
+            Container::Circle { x, y, radius } => {
+                Some((Vec2::new(x, y), Vec2::new(radius, radius)))
+            }
+            Container::Ellipse {
+                x,
+                y,
+                radius_x,
+                radius_y,
+            } => Some((Vec2::new(x, y), Vec2::new(radius_x, radius_y))),
+        }
+    }
+
+    /// Axis-aligned `(min, max)` of a round container. The window has no
+    /// fixed bounds of its own, so it reports an empty box at the origin.
+    pub fn bounds(&self) -> (Vec2<f32>, Vec2<f32>) {
+        match self.ellipse() {
+            Some((center, radii)) => (center - radii, center + radii),
+            None => (Vec2::new(0.0, 0.0), Vec2::new(0.0, 0.0)),
+        }
+    }
+
+    /// Whether a particle of `radius` at `point` lies fully inside a round
+    /// container. Always true for the window.
+    pub fn contains(&self, point: Vec2<f32>, radius: f32) -> bool {
+        match self.ellipse() {
+            Some((center, radii)) => {
+                let a = radii.x - radius;
+                let b = radii.y - radius;
+                let d = point - center;
+                a > 0.0 && b > 0.0 && (d.x / a).powi(2) + (d.y / b).powi(2) <= 1.0
+            }
+            None => true,
+        }
+    }
+}
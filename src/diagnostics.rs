@@ -0,0 +1,180 @@
+use crate::container::Boundary;
+use crate::grid::Grid;
+use crate::verlet_object::{Solver, VerletObject, NEIGHBOURS};
+use crate::world::World;
+use cgmath::{InnerSpace, Vector2 as Vec2};
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+/// Health measures of one frame, for telling whether a parameter change made
+/// the simulation calmer or wilder. Velocities are in units per second.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Diagnostics {
+    pub frame: u32,
+    /// Simulated seconds since the run started.
+    pub time: f32,
+    /// Sum of `0.5 * m * v^2`.
+    pub kinetic_energy: f32,
+    /// Sum of `m * g * h`, with heights measured against gravity from the
+    /// window's bottom-right corner, so resting snow sits near zero.
+    pub potential_energy: f32,
+    /// Sum of `m * v`.
+    pub momentum: Vec2<f32>,
+    pub max_speed: f32,
+    /// Touching or overlapping particle pairs.
+    pub contacts: usize,
+    /// Mean overlap of those pairs.
+    pub mean_penetration: f32,
+    /// Particles removed so far for going NaN or infinite.
+    pub removed_invalid: usize,
+    /// Updates the solver's explosion guard has recovered from so far.
+    pub recoveries: usize,
+}
+
+impl Diagnostics {
+    /// Column names matching [`Diagnostics::csv_row`].
+    pub const CSV_HEADER: &'static str = "frame,time,kinetic_energy,potential_energy,\
+        momentum_x,momentum_y,max_speed,contacts,mean_penetration,removed_invalid,recoveries";
+
+    /// Measures `world` as it is after a frame of length `dt`.
+    pub fn measure(world: &World, frame: u32, time: f32, dt: f32) -> Self {
+        let substep_dt = dt / world.solver.substeps.max(1) as f32;
+        let gravity = world.solver.gravity;
+        let floor = Vec2::new(world.solver.width as f32, world.solver.height as f32);
+
+        let mut kinetic_energy = 0.0;
+        let mut potential_energy = 0.0;
+        let mut momentum = Vec2::new(0.0, 0.0);
+        let mut max_speed = 0.0f32;
+        for p in world.particles() {
+            let v = p.velocity() / substep_dt;
+            kinetic_energy += 0.5 * p.mass * v.magnitude2();
+            potential_energy += p.mass * gravity.dot(floor - p.position_current);
+            momentum += v * p.mass;
+            max_speed = max_speed.max(v.magnitude());
+        }
+
+        let (contacts, total_penetration) = contacts(
+            &world.solver,
+            world.particles(),
+            &mut Grid::default(),
+            &mut Vec::new(),
+        );
+        Self {
+            frame,
+            time,
+            kinetic_energy,
+            potential_energy,
+            momentum,
+            max_speed,
+            contacts,
+            mean_penetration: if contacts == 0 {
+                0.0
+            } else {
+                total_penetration / contacts as f32
+            },
+            removed_invalid: world.removed_invalid,
+            recoveries: world.solver.recoveries,
+        }
+    }
+
+    pub fn csv_row(&self) -> String {
+        format!(
+            "{},{},{},{},{},{},{},{},{},{},{}",
+            self.frame,
+            self.time,
+            self.kinetic_energy,
+            self.potential_energy,
+            self.momentum.x,
+            self.momentum.y,
+            self.max_speed,
+            self.contacts,
+            self.mean_penetration,
+            self.removed_invalid,
+            self.recoveries
+        )
+    }
+}
+
+/// Total kinetic energy of `world` after a frame of length `dt`.
+pub fn kinetic_energy(world: &World, dt: f32) -> f32 {
+    world.solver.kinetic_energy(world.particles(), dt)
+}
+
+/// Number of touching pairs and their summed overlap. Uses one grid sized for
+/// the largest particle, which is slower than the solver's but simpler;
+/// `grid` and `levels` are scratch space.
+pub(crate) fn contacts(
+    solver: &Solver,
+    particles: &[VerletObject],
+    grid: &mut Grid,
+    levels: &mut Vec<u8>,
+) -> (usize, f32) {
+    let max_radius = particles
+        .iter()
+        .map(|p| p.radius)
+        .filter(|r| r.is_finite())
+        .fold(0.0f32, f32::max);
+    if particles.is_empty() || max_radius <= 0.0 {
+        return (0, 0.0);
+    }
+
+    levels.clear();
+    levels.resize(particles.len(), 0);
+    grid.rebuild(
+        particles,
+        levels,
+        0,
+        2.0 * max_radius,
+        (solver.width as f32, solver.height as f32),
+        solver.boundary == Boundary::Periodic,
+    );
+
+    let mut count = 0;
+    let mut penetration = 0.0;
+    for (x, y) in grid.occupied() {
+        let cell = grid.cell(x, y);
+        for (dx, dy) in NEIGHBOURS {
+            for &i in cell {
+                for &j in grid.cell(x + dx, y + dy) {
+                    // Pairs within one cell are seen twice; count them once
+                    if i == j || ((dx, dy) == (0, 0) && i > j) {
+                        continue;
+                    }
+                    let (a, b) = (&particles[i as usize], &particles[j as usize]);
+                    let axis = a.position_current - b.position_current;
+                    let dist = (axis + solver.periodic_shift(axis)).magnitude();
+                    let overlap = a.radius + b.radius - dist;
+                    if overlap >= 0.0 {
+                        count += 1;
+                        penetration += overlap;
+                    }
+                }
+            }
+        }
+    }
+    (count, penetration)
+}
+
+/// Streams [`Diagnostics`] rows to a CSV file.
+pub struct CsvWriter {
+    out: BufWriter<File>,
+}
+
+impl CsvWriter {
+    /// Creates `path` and writes the header.
+    pub fn create(path: &Path) -> io::Result<Self> {
+        let mut out = BufWriter::new(File::create(path)?);
+        writeln!(out, "{}", Diagnostics::CSV_HEADER)?;
+        Ok(Self { out })
+    }
+
+    pub fn write(&mut self, diagnostics: &Diagnostics) -> io::Result<()> {
+        writeln!(self.out, "{}", diagnostics.csv_row())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.out.flush()
+    }
+}
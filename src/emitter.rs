@@ -0,0 +1,195 @@
+use crate::verlet_object::VerletObject;
+use crate::world::World;
+use cgmath::Vector2 as Vec2;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+/// Where an [`Emitter`] places new particles. Points are `[x, y]` pairs in
+/// world coordinates.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EmitterShape {
+    Point {
+        x: f32,
+        y: f32,
+    },
+    /// Anywhere along the segment from `a` to `b`.
+    Segment {
+        a: (f32, f32),
+        b: (f32, f32),
+    },
+    /// Anywhere inside the rectangle from `min` to `max`.
+    Rect {
+        min: (f32, f32),
+        max: (f32, f32),
+    },
+}
+
+impl EmitterShape {
+    /// A random position within the shape.
+    pub fn sample<R: Rng>(&self, rng: &mut R) -> Vec2<f32> {
+        match *self {
+            EmitterShape::Point { x, y } => Vec2::new(x, y),
+            EmitterShape::Segment { a, b } => {
+                let t: f32 = rng.random();
+                Vec2::new(a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+            }
+            EmitterShape::Rect { min, max } => {
+                let (tx, ty): (f32, f32) = (rng.random(), rng.random());
+                Vec2::new(min.0 + (max.0 - min.0) * tx, min.1 + (max.1 - min.1) * ty)
+            }
+        }
+    }
+
+    pub fn center(&self) -> (f32, f32) {
+        match *self {
+            EmitterShape::Point { x, y } => (x, y),
+            EmitterShape::Segment { a: p, b: q } | EmitterShape::Rect { min: p, max: q } => {
+                ((p.0 + q.0) / 2.0, (p.1 + q.1) / 2.0)
+            }
+        }
+    }
+
+    /// The same shape moved so its center is at `(x, y)`.
+    pub fn moved_to(&self, x: f32, y: f32) -> Self {
+        let (cx, cy) = self.center();
+        let shift = |p: (f32, f32)| (p.0 + x - cx, p.1 + y - cy);
+        match *self {
+            EmitterShape::Point { .. } => EmitterShape::Point { x, y },
+            EmitterShape::Segment { a, b } => EmitterShape::Segment {
+                a: shift(a),
+                b: shift(b),
+            },
+            EmitterShape::Rect { min, max } => EmitterShape::Rect {
+                min: shift(min),
+                max: shift(max),
+            },
+        }
+    }
+}
+
+/// A continuous source of particles, ticked by [`World::step`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Emitter {
+    pub shape: EmitterShape,
+    /// Particles per second.
+    pub rate: f32,
+    /// Mean starting velocity in units per second.
+    pub velocity: (f32, f32),
+    /// Each velocity component is off the mean by up to this much either way.
+    pub velocity_spread: f32,
+    /// Radii are picked evenly from `min_radius..=max_radius`.
+    pub min_radius: f32,
+    pub max_radius: f32,
+    /// Colour of the emitted particles, kept instead of the usual colouring
+    /// by speed.
+    pub color: (u8, u8, u8),
+    /// Stop after emitting this many particles.
+    pub max_particles: Option<usize>,
+    /// Seeds the emitter's random numbers. Emitters from the config are mixed
+    /// with the run's seed, so it only needs to differ between emitters.
+    pub seed: u64,
+    /// Fraction of a particle owed from earlier ticks. Saved in snapshots
+    /// along with `emitted`, so a reloaded emitter carries on where it was.
+    accumulator: f32,
+    /// Particles emitted so far: counts towards `max_particles` and picks
+    /// each particle's random numbers.
+    emitted: usize,
+}
+
+impl Default for Emitter {
+    fn default() -> Self {
+        Self {
+            shape: EmitterShape::Point { x: 0.0, y: 0.0 },
+            rate: 30.0,
+            velocity: (0.0, 0.0),
+            velocity_spread: 0.0,
+            min_radius: 5.0,
+            max_radius: 5.0,
+            color: (255, 255, 255),
+            max_particles: None,
+            seed: 0,
+            accumulator: 0.0,
+            emitted: 0,
+        }
+    }
+}
+
+impl Emitter {
+    /// A copy of this emitter with `seed` that has not emitted anything yet.
+    pub fn with_seed(&self, seed: u64) -> Self {
+        Self {
+            seed,
+            accumulator: 0.0,
+            emitted: 0,
+            ..*self
+        }
+    }
+
+    /// Adds the particles due over `dt` seconds to `world`. Returns how many.
+    pub fn emit(&mut self, world: &mut World, dt: f32) -> usize {
+        self.accumulator += self.rate * dt;
+        let mut count = self.accumulator.floor() as usize;
+        self.accumulator -= count as f32;
+        if let Some(max) = self.max_particles {
+            count = count.min(max.saturating_sub(self.emitted));
+        }
+
+        // Verlet velocity is the displacement over one substep
+        let substep_dt = dt / world.solver.substeps.max(1) as f32;
+        for _ in 0..count {
+            // One generator per particle, so the stream doesn't depend on how
+            // many ticks the particles were spread over
+            let mut rng = StdRng::seed_from_u64(
+                self.seed ^ (self.emitted as u64).wrapping_mul(0x9e37_79b9_7f4a_7c15),
+            );
+            let pos = self.shape.sample(&mut rng);
+            let mut spread = || (rng.random::<f32>() * 2.0 - 1.0) * self.velocity_spread;
+            let velocity = Vec2::new(self.velocity.0 + spread(), self.velocity.1 + spread());
+            let radius =
+                self.min_radius + (self.max_radius - self.min_radius) * rng.random::<f32>();
+
+            let mut particle = VerletObject::new(
+                pos,
+                pos - velocity * substep_dt,
+                Vec2::new(0.0, 0.0),
+                radius,
+                self.color,
+                false,
+            );
+            particle.material = world.pick_material(&mut rng);
+            particle.fixed_color = true;
+            world.add_particle(particle);
+            self.emitted += 1;
+        }
+        count
+    }
+
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if !(self.rate >= 0.0 && self.rate.is_finite()) {
+            problems.push(format!(
+                "emitter rate must not be negative, got {}",
+                self.rate
+            ));
+        }
+        if !(self.velocity_spread >= 0.0 && self.velocity_spread.is_finite()) {
+            problems.push(format!(
+                "emitter velocity_spread must not be negative, got {}",
+                self.velocity_spread
+            ));
+        }
+        if !(self.min_radius > 0.0
+            && self.min_radius <= self.max_radius
+            && self.max_radius.is_finite())
+        {
+            problems.push(format!(
+                "emitter needs 0 < min_radius <= max_radius, got {}..{}",
+                self.min_radius, self.max_radius
+            ));
+        }
+        problems
+    }
+}
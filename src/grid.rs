@@ -0,0 +1,261 @@
+use crate::verlet_object::VerletObject;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// How the solver finds particles close enough to collide.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Broadphase {
+    /// Flat [`Grid`] rebuilt by counting sort, reusing its buffers.
+    #[default]
+    Grid,
+    /// A map of cell vectors rebuilt from scratch every substep. Kept to
+    /// compare against in `--bench`; a periodic boundary always uses the grid.
+    Tree,
+}
+
+/// Uniform grid of particle indices. Each rebuild counting-sorts the
+/// particles into contiguous per-cell ranges of one array, so once the
+/// buffers have grown to fit, stepping no longer allocates.
+#[derive(Clone, Debug, Default)]
+pub struct Grid {
+    cell_size: (f32, f32),
+    /// Positions are clamped into this box before finding their cell.
+    bounds: ((f32, f32), (f32, f32)),
+    /// Size of the world when it wraps around. Positions are wrapped into it
+    /// instead of clamped, and neighbours of the edge cells are found on the
+    /// opposite edge.
+    wrap: Option<(f32, f32)>,
+    wrap_columns: bool,
+    wrap_rows: bool,
+    /// Cell coordinates of the grid's first column and row.
+    min: (i32, i32),
+    columns: i32,
+    rows: i32,
+    /// Cell of each particle, by particle index.
+    cells: Vec<u32>,
+    /// `entries[starts[c]..starts[c + 1]]` are the particles in cell `c`.
+    starts: Vec<u32>,
+    entries: Vec<u32>,
+    cursor: Vec<u32>,
+}
+
+/// A grid never has more cells than this per particle in it; sparse
+/// particles get bigger cells instead.
+const MAX_CELLS_PER_PARTICLE: f32 = 4.0;
+
+/// How many world sizes past each edge the grid follows particles.
+const REACH: f32 = 8.0;
+
+impl Grid {
+    /// Sorts the particles on `level` of `level_of` into cells of at least
+    /// `cell_size`. `home` is the size of the world; particles more than
+    /// [`REACH`] worlds away are clamped into the outermost cells so a runaway
+    /// particle can't blow up the grid. With `periodic`, the grid instead
+    /// covers exactly `home` and wraps around at its edges.
+    pub fn rebuild(
+        &mut self,
+        particles: &[VerletObject],
+        level_of: &[u8],
+        level: u8,
+        cell_size: f32,
+        home: (f32, f32),
+        periodic: bool,
+    ) {
+        self.bounds = (
+            (-REACH * home.0, -REACH * home.1),
+            ((REACH + 1.0) * home.0, (REACH + 1.0) * home.1),
+        );
+        self.wrap = periodic.then_some(home);
+
+        let mut count = 0;
+        let (mut min_x, mut min_y) = (f32::INFINITY, f32::INFINITY);
+        let (mut max_x, mut max_y) = (f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for (p, _) in particles.iter().zip(level_of).filter(|(_, &l)| l == level) {
+            let (x, y) = self.position(p);
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+            count += 1;
+        }
+        if min_x > max_x || min_y > max_y {
+            // Nothing but NaN; they all share one cell
+            (min_x, min_y, max_x, max_y) = (0.0, 0.0, 0.0, 0.0);
+        }
+        self.cells.clear();
+        self.entries.clear();
+        if count == 0 {
+            self.columns = 0;
+            self.rows = 0;
+            self.starts.clear();
+            self.starts.push(0);
+            return;
+        }
+
+        // Bigger cells only mean more candidate pairs, never missed ones
+        let max_cells = MAX_CELLS_PER_PARTICLE * count as f32;
+        match self.wrap {
+            Some((width, height)) => {
+                // Whole cells across the world, so the last meets the first
+                let mut columns = (width / cell_size).floor().max(1.0);
+                let mut rows = (height / cell_size).floor().max(1.0);
+                let scale = (columns * rows / max_cells).sqrt();
+                if scale > 1.0 {
+                    columns = (columns / scale).floor().max(1.0);
+                    rows = (rows / scale).floor().max(1.0);
+                }
+                // Under three, a cell's neighbours on either side are the
+                // same cell; one cell across then holds every neighbour
+                let (columns, rows) = (columns as i32, rows as i32);
+                self.wrap_columns = columns >= 3;
+                self.wrap_rows = rows >= 3;
+                self.columns = if self.wrap_columns { columns } else { 1 };
+                self.rows = if self.wrap_rows { rows } else { 1 };
+                self.cell_size = (width / self.columns as f32, height / self.rows as f32);
+                self.min = (0, 0);
+            }
+            None => {
+                let area = (max_x - min_x + cell_size) * (max_y - min_y + cell_size);
+                let size = cell_size.max((area / max_cells).sqrt());
+                self.cell_size = (size, size);
+                self.wrap_columns = false;
+                self.wrap_rows = false;
+                let (min_x, min_y) = self.cell_at(min_x, min_y);
+                let (max_x, max_y) = self.cell_at(max_x, max_y);
+                self.min = (min_x, min_y);
+                self.columns = max_x - min_x + 1;
+                self.rows = max_y - min_y + 1;
+            }
+        }
+
+        // Column-major, so walking cells in order matches sorting by (x, y)
+        let cell_count = (self.columns * self.rows) as usize;
+        self.starts.clear();
+        self.starts.resize(cell_count + 1, 0);
+        for (p, &l) in particles.iter().zip(level_of) {
+            if l != level {
+                self.cells.push(u32::MAX);
+                continue;
+            }
+            let (x, y) = self.cell_of(p);
+            let column = (x - self.min.0).clamp(0, self.columns - 1);
+            let row = (y - self.min.1).clamp(0, self.rows - 1);
+            let index = (column * self.rows + row) as u32;
+            self.cells.push(index);
+            self.starts[index as usize + 1] += 1;
+        }
+        for c in 0..cell_count {
+            self.starts[c + 1] += self.starts[c];
+        }
+
+        self.cursor.clear();
+        self.cursor.extend_from_slice(&self.starts[..cell_count]);
+        self.entries.resize(count, 0);
+        for (i, &index) in self.cells.iter().enumerate() {
+            if index == u32::MAX {
+                continue;
+            }
+            let slot = &mut self.cursor[index as usize];
+            self.entries[*slot as usize] = i as u32;
+            *slot += 1;
+        }
+    }
+
+    /// Where `p` is placed on the grid: wrapped into the world when it is
+    /// periodic, otherwise clamped to [`REACH`].
+    fn position(&self, p: &VerletObject) -> (f32, f32) {
+        let (x, y) = (p.position_current.x, p.position_current.y);
+        match self.wrap {
+            Some((width, height)) => (x.rem_euclid(width), y.rem_euclid(height)),
+            None => {
+                let (low, high) = self.bounds;
+                (x.clamp(low.0, high.0), y.clamp(low.1, high.1))
+            }
+        }
+    }
+
+    fn cell_at(&self, x: f32, y: f32) -> (i32, i32) {
+        (
+            (x / self.cell_size.0).floor() as i32,
+            (y / self.cell_size.1).floor() as i32,
+        )
+    }
+
+    /// Cell coordinates `p` falls in, whether or not it is on this grid.
+    /// Like on rebuild, positions far outside the world are pulled in first.
+    pub fn cell_of(&self, p: &VerletObject) -> (i32, i32) {
+        let (x, y) = self.position(p);
+        self.cell_at(x, y)
+    }
+
+    /// Whether the last column's right-hand neighbours are the first column.
+    pub fn wraps_columns(&self) -> bool {
+        self.wrap_columns
+    }
+
+    /// Occupied cells in order, as their cell coordinates.
+    pub fn occupied(&self) -> impl Iterator<Item = (i32, i32)> + '_ {
+        let mut last = None;
+        self.entries.iter().filter_map(move |&i| {
+            let cell = self.cells[i as usize];
+            if last == Some(cell) {
+                return None;
+            }
+            last = Some(cell);
+            let cell = cell as i32;
+            Some((cell / self.rows + self.min.0, cell % self.rows + self.min.1))
+        })
+    }
+
+    pub fn columns(&self) -> i32 {
+        self.columns
+    }
+
+    pub fn rows(&self) -> i32 {
+        self.rows
+    }
+
+    /// Particle indices grouped by cell, column by column.
+    pub fn entries(&self) -> &[u32] {
+        &self.entries
+    }
+
+    /// Where grid column `column` starts in [`Grid::entries`]. `columns()`
+    /// gives the end of the last one.
+    pub fn column_start(&self, column: i32) -> usize {
+        self.starts[(column * self.rows) as usize] as usize
+    }
+
+    /// Like [`Grid::cell_range`], but rows past either end wrap around on a
+    /// periodic grid.
+    pub fn wrapped_row_range(&self, column: i32, row: i32) -> Range<usize> {
+        if self.wrap_rows {
+            self.cell_range(column, row.rem_euclid(self.rows))
+        } else {
+            self.cell_range(column, row)
+        }
+    }
+
+    /// Range of [`Grid::entries`] in the cell at `column`, `row`, empty
+    /// outside the grid.
+    pub fn cell_range(&self, column: i32, row: i32) -> Range<usize> {
+        if column < 0 || row < 0 || column >= self.columns || row >= self.rows {
+            return 0..0;
+        }
+        let cell = (column * self.rows + row) as usize;
+        self.starts[cell] as usize..self.starts[cell + 1] as usize
+    }
+
+    /// Particle indices in the cell at `(x, y)`, empty outside the grid.
+    pub fn cell(&self, x: i32, y: i32) -> &[u32] {
+        let (mut column, mut row) = (x - self.min.0, y - self.min.1);
+        if self.wrap_columns {
+            column = column.rem_euclid(self.columns);
+        }
+        if self.wrap_rows {
+            row = row.rem_euclid(self.rows);
+        }
+        &self.entries[self.cell_range(column, row)]
+    }
+}
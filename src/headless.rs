@@ -0,0 +1,126 @@
+use crate::diagnostics::kinetic_energy;
+use crate::world::World;
+use cgmath::Vector2 as Vec2;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+/// Result of stepping a world for a fixed number of frames without a window.
+pub struct HeadlessSummary {
+    pub frames: u32,
+    pub particle_count: usize,
+    /// Sum of `0.5 * m * v^2` over all particles, with `v` in units per second.
+    pub kinetic_energy: f32,
+    pub bounding_box: Option<(Vec2<f32>, Vec2<f32>)>,
+    pub state_hash: u64,
+    /// Fewest and most substeps any frame used.
+    pub substeps: (i32, i32),
+    pub total_time: Duration,
+    pub min_step: Duration,
+    pub mean_step: Duration,
+    pub max_step: Duration,
+}
+
+/// Steps `world` for `frames` frames of length `dt` and summarizes the result.
+pub fn run(world: &mut World, frames: u32, dt: f32) -> HeadlessSummary {
+    run_with(world, frames, dt, |world, _| world.step(dt), |_, ()| {})
+}
+
+/// Like [`run`], but `frame` is called with the frame index to advance the
+/// world, e.g. to feed recorded input before stepping. `after` then gets the
+/// world and what `frame` returned outside the timed part, for work such as
+/// writing diagnostics that shouldn't count towards the step times.
+pub fn run_with<T, F, A>(
+    world: &mut World,
+    frames: u32,
+    dt: f32,
+    mut frame: F,
+    mut after: A,
+) -> HeadlessSummary
+where
+    F: FnMut(&mut World, u32) -> T,
+    A: FnMut(&World, T),
+{
+    let mut total_time = Duration::ZERO;
+    let mut min_step = Duration::MAX;
+    let mut max_step = Duration::ZERO;
+    let mut substeps = (i32::MAX, i32::MIN);
+
+    for i in 0..frames {
+        let start = Instant::now();
+        let result = frame(world, i);
+        let elapsed = start.elapsed();
+        after(world, result);
+        substeps.0 = substeps.0.min(world.solver.substeps);
+        substeps.1 = substeps.1.max(world.solver.substeps);
+
+        total_time += elapsed;
+        min_step = min_step.min(elapsed);
+        max_step = max_step.max(elapsed);
+    }
+
+    HeadlessSummary {
+        frames,
+        particle_count: world.particle_count(),
+        kinetic_energy: kinetic_energy(world, dt),
+        bounding_box: world.bounding_box(),
+        state_hash: world.state_hash(),
+        substeps: if frames == 0 {
+            (world.solver.substeps, world.solver.substeps)
+        } else {
+            substeps
+        },
+        total_time,
+        min_step: if frames == 0 {
+            Duration::ZERO
+        } else {
+            min_step
+        },
+        mean_step: if frames == 0 {
+            Duration::ZERO
+        } else {
+            total_time / frames
+        },
+        max_step,
+    }
+}
+
+impl HeadlessSummary {
+    /// Particles advanced one frame per second of step time.
+    pub fn particles_per_second(&self) -> f64 {
+        let seconds = self.total_time.as_secs_f64();
+        if seconds == 0.0 {
+            return 0.0;
+        }
+        self.particle_count as f64 * self.frames as f64 / seconds
+    }
+}
+
+impl fmt::Display for HeadlessSummary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "frames: {}", self.frames)?;
+        writeln!(f, "particles: {}", self.particle_count)?;
+        writeln!(f, "kinetic_energy: {}", self.kinetic_energy)?;
+        match self.bounding_box {
+            Some((min, max)) => writeln!(
+                f,
+                "bounding_box: ({}, {}) - ({}, {})",
+                min.x, min.y, max.x, max.y
+            )?,
+            None => writeln!(f, "bounding_box: none")?,
+        }
+        writeln!(f, "state_hash: {:016x}", self.state_hash)?;
+        if self.substeps.0 == self.substeps.1 {
+            writeln!(f, "substeps: {}", self.substeps.0)?;
+        } else {
+            writeln!(f, "substeps: {}..{}", self.substeps.0, self.substeps.1)?;
+        }
+        writeln!(
+            f,
+            "total_time_ms: {:.3}",
+            self.total_time.as_secs_f64() * 1e3
+        )?;
+        writeln!(f, "min_step_ms: {:.3}", self.min_step.as_secs_f64() * 1e3)?;
+        writeln!(f, "mean_step_ms: {:.3}", self.mean_step.as_secs_f64() * 1e3)?;
+        writeln!(f, "max_step_ms: {:.3}", self.max_step.as_secs_f64() * 1e3)
+    }
+}
@@ -0,0 +1,339 @@
+use crate::cloth::Cloth;
+use crate::emitter::Emitter;
+use crate::snapshot::{self, check_version, SnapshotError, SnapshotFormat, VersionProbe};
+use crate::soft_body::SoftBody;
+use crate::timestep::{FixedTimestep, MAX_TIME_SCALE, MIN_TIME_SCALE};
+use crate::verlet_object::VerletObject;
+use crate::world::World;
+use cgmath::{InnerSpace, Vector2 as Vec2};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Bumped whenever the layout of [`InputRecording`] changes incompatibly.
+pub const RECORDING_VERSION: u32 = 5;
+
+/// Where [`FrameInput::save_json_pressed`] and
+/// [`FrameInput::save_binary_pressed`] write their snapshots.
+pub const QUICK_SAVE_JSON: &str = "snowglobe.json";
+pub const QUICK_SAVE_BINARY: &str = "snowglobe.snap";
+
+/// Everything the app reacts to in a single frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct FrameInput {
+    /// Previous window position minus the current one.
+    pub window_delta: (f32, f32),
+    pub mouse: (i32, i32),
+    pub left_down: bool,
+    pub right_down: bool,
+    pub scroll: f32,
+    /// P held
+    pub play_down: bool,
+    /// S held
+    pub stop_down: bool,
+    pub screen_size: (i32, i32),
+    /// C pressed this frame
+    pub cloth_pressed: bool,
+    /// Middle mouse button pressed this frame
+    pub soft_body_pressed: bool,
+    /// Real seconds since the previous frame
+    pub frame_time: f32,
+    /// Minus pressed this frame: halve the time scale
+    pub slower_pressed: bool,
+    /// Equals pressed this frame: double the time scale
+    pub faster_pressed: bool,
+    /// Period pressed this frame: step once while paused
+    pub step_pressed: bool,
+    /// E pressed this frame: place an emitter at the cursor
+    pub emitter_pressed: bool,
+    /// F5 pressed this frame: save a JSON snapshot
+    pub save_json_pressed: bool,
+    /// F6 pressed this frame: save a binary snapshot
+    pub save_binary_pressed: bool,
+    /// F9 pressed this frame: reload the last snapshot saved or loaded
+    pub load_pressed: bool,
+}
+
+/// Knobs that shape how input turns into forces and new particles.
+#[derive(Clone, Copy, Debug)]
+pub struct InputSettings {
+    pub particle_size: f32,
+    pub size_variance: i32,
+    pub movement_dampening: f32,
+    /// Template for cloths dropped at the cursor.
+    pub cloth: Cloth,
+    /// Template for soft bodies dropped at the cursor.
+    pub soft_body: SoftBody,
+    /// Template for emitters placed at the cursor.
+    pub emitter: Emitter,
+}
+
+/// Interaction state carried between frames.
+pub struct Controls {
+    pub settings: InputSettings,
+    pub playing: bool,
+    /// Radius of the mouse tool. Positive pushes and spawns pinned particles,
+    /// negative pulls and spawns loose ones.
+    pub fall_off: f32,
+    pub timestep: FixedTimestep,
+    /// Snapshot [`FrameInput::load_pressed`] reloads: the last quick save, or
+    /// the file the run started from.
+    pub last_save: Option<PathBuf>,
+}
+
+impl Controls {
+    pub fn new(settings: InputSettings) -> Self {
+        Self {
+            settings,
+            playing: true,
+            fall_off: 100.0,
+            timestep: FixedTimestep::new(1.0 / 60.0, 5),
+            last_save: None,
+        }
+    }
+
+    /// Applies one frame of input to `world`. Does not step the simulation.
+    /// Quick saves and loads come last; if one fails, the rest of the input
+    /// has still been applied.
+    pub fn apply<R: Rng>(
+        &mut self,
+        world: &mut World,
+        rng: &mut R,
+        input: &FrameInput,
+    ) -> Result<(), SnapshotError> {
+        let particle_size = self.settings.particle_size;
+        let size_variance = self.settings.size_variance;
+        let (mouse_x, mouse_y) = input.mouse;
+
+        if input.window_delta != (0.0, 0.0) {
+            let force_vector = Vec2::new(input.window_delta.0, input.window_delta.1);
+            let n = force_vector / force_vector.magnitude();
+            world.apply_force(n / self.settings.movement_dampening);
+        }
+
+        if input.right_down {
+            for i in 0..(if self.fall_off < 0.0 { 10 } else { 1 }) {
+                let mut particle = VerletObject::new(
+                    Vec2::new((mouse_x + i) as f32, (mouse_y + i) as f32),
+                    Vec2::new((mouse_x + i) as f32, (mouse_y + i) as f32),
+                    Vec2::new(0.0, 0.0),
+                    if size_variance != 0 && self.fall_off < 0.0 {
+                        (particle_size + (rng.random_range(-size_variance..size_variance) as f32))
+                            .abs()
+                    } else {
+                        particle_size
+                    },
+                    (255, 255, 255),
+                    self.fall_off > 0.0,
+                );
+                particle.material = world.pick_material(rng);
+                world.add_particle(particle);
+            }
+        }
+        if input.left_down {
+            world.apply_point_force(Vec2::new(mouse_x as f32, mouse_y as f32), self.fall_off);
+        }
+
+        if input.cloth_pressed {
+            let cloth = self.settings.cloth;
+            let half_width = (cloth.columns.saturating_sub(1)) as f32 * cloth.spacing / 2.0;
+            Cloth {
+                x: mouse_x as f32 - half_width,
+                y: mouse_y as f32,
+                ..cloth
+            }
+            .spawn(world);
+        }
+        if input.soft_body_pressed {
+            SoftBody {
+                x: mouse_x as f32,
+                y: mouse_y as f32,
+                ..self.settings.soft_body
+            }
+            .spawn(world);
+        }
+
+        if input.emitter_pressed {
+            let template = self.settings.emitter;
+            let mut emitter = template.with_seed(rng.random());
+            emitter.shape = template.shape.moved_to(mouse_x as f32, mouse_y as f32);
+            world.emitters.push(emitter);
+        }
+
+        self.fall_off += 5.0 * input.scroll;
+
+        world.solver.width = input.screen_size.0;
+        world.solver.height = input.screen_size.1;
+
+        if input.play_down {
+            self.playing = true;
+        }
+        if input.stop_down {
+            self.playing = false;
+        }
+
+        for (pressed, path) in [
+            (input.save_json_pressed, QUICK_SAVE_JSON),
+            (input.save_binary_pressed, QUICK_SAVE_BINARY),
+        ] {
+            if pressed {
+                let path = PathBuf::from(path);
+                snapshot::save(world, &path)?;
+                self.last_save = Some(path);
+            }
+        }
+        if input.load_pressed {
+            if let Some(path) = &self.last_save {
+                *world = snapshot::load(path)?;
+                self.timestep.reset();
+            }
+        }
+        Ok(())
+    }
+
+    /// Steps `world` for one frame: the frame's real time, scaled, in fixed
+    /// steps while playing, or a single step on request while paused.
+    /// Returns the steps taken.
+    pub fn advance(&mut self, world: &mut World, input: &FrameInput) -> u32 {
+        let timestep = &mut self.timestep;
+        if input.slower_pressed {
+            timestep.time_scale = (timestep.time_scale / 2.0).max(MIN_TIME_SCALE);
+        }
+        if input.faster_pressed {
+            timestep.time_scale = (timestep.time_scale * 2.0).min(MAX_TIME_SCALE);
+        }
+
+        if self.playing {
+            timestep.run(world, input.frame_time)
+        } else if input.step_pressed {
+            timestep.single_step(world);
+            1
+        } else {
+            0
+        }
+    }
+}
+
+/// A recorded input session. Replaying it with the same seed and arguments
+/// reproduces the original run.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct InputRecording {
+    pub version: u32,
+    pub seed: u64,
+    pub frames: Vec<FrameInput>,
+}
+
+/// What a recording file starts with; the frames follow one at a time.
+#[derive(Serialize, Deserialize)]
+struct RecordingHeader {
+    version: u32,
+    seed: u64,
+}
+
+impl InputRecording {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            version: RECORDING_VERSION,
+            seed,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Writes the recording to `path`, choosing the format from the file extension.
+    pub fn save(&self, path: &Path) -> Result<(), SnapshotError> {
+        let mut writer = RecordingWriter::create(path, self.seed)?;
+        for frame in self.frames.iter() {
+            writer.write(frame)?;
+        }
+        Ok(())
+    }
+
+    pub fn load(path: &Path) -> Result<Self, SnapshotError> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    /// Parses a recording in either format. Reading stops at the first frame
+    /// that doesn't parse, such as one cut short when the app crashed.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        let mut recording = match snapshot::binary_body(bytes, RECORDING_VERSION)? {
+            Some(mut body) => {
+                let mut recording = Self::new(bincode::deserialize_from(&mut body)?);
+                while let Ok(frame) = bincode::deserialize_from(&mut body) {
+                    recording.frames.push(frame);
+                }
+                recording
+            }
+            None => {
+                let text = String::from_utf8_lossy(bytes);
+                let mut lines = text.lines();
+                let header: RecordingHeader = match serde_json::from_str(lines.next().unwrap_or(""))
+                {
+                    Ok(header) => header,
+                    // Older recordings were one JSON document; report their version
+                    Err(e) => match serde_json::from_slice::<VersionProbe>(bytes) {
+                        Ok(probe) => {
+                            check_version(probe.version, RECORDING_VERSION)?;
+                            return Err(e.into());
+                        }
+                        Err(_) => return Err(e.into()),
+                    },
+                };
+                check_version(header.version, RECORDING_VERSION)?;
+                let mut recording = Self::new(header.seed);
+                recording
+                    .frames
+                    .extend(lines.map_while(|line| serde_json::from_str::<FrameInput>(line).ok()));
+                recording
+            }
+        };
+        recording.version = RECORDING_VERSION;
+        Ok(recording)
+    }
+}
+
+/// Streams an [`InputRecording`] to a file as it is made, so a crash loses
+/// at most the frame being written. JSON files hold the header and then one
+/// frame per line; binary ones the header and then the bincode frames.
+pub struct RecordingWriter {
+    out: BufWriter<File>,
+    format: SnapshotFormat,
+}
+
+impl RecordingWriter {
+    /// Creates `path`, choosing the format from the file extension, and
+    /// writes the header.
+    pub fn create(path: &Path, seed: u64) -> Result<Self, SnapshotError> {
+        let format = SnapshotFormat::from_path(path);
+        let mut out = BufWriter::new(File::create(path)?);
+        match format {
+            SnapshotFormat::Json => {
+                let header = RecordingHeader {
+                    version: RECORDING_VERSION,
+                    seed,
+                };
+                serde_json::to_writer(&mut out, &header)?;
+                out.write_all(b"\n")?;
+            }
+            SnapshotFormat::Binary => {
+                out.write_all(&snapshot::encode(&seed, RECORDING_VERSION, format)?)?;
+            }
+        }
+        out.flush()?;
+        Ok(Self { out, format })
+    }
+
+    /// Appends `frame` and flushes it to disk.
+    pub fn write(&mut self, frame: &FrameInput) -> Result<(), SnapshotError> {
+        match self.format {
+            SnapshotFormat::Json => {
+                serde_json::to_writer(&mut self.out, frame)?;
+                self.out.write_all(b"\n")?;
+            }
+            SnapshotFormat::Binary => bincode::serialize_into(&mut self.out, frame)?,
+        }
+        self.out.flush()?;
+        Ok(())
+    }
+}
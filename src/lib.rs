@@ -0,0 +1,34 @@
+//! Headless Verlet particle simulation used by the Digital Snowglobe.
+//!
+//! Nothing in this crate depends on raylib; the windowed app in `main.rs` is
+//! only built with the `gui` feature.
+
+pub mod cloth;
+pub mod config;
+pub mod constraint;
+pub mod container;
+pub mod diagnostics;
+pub mod emitter;
+pub(crate) mod grid;
+pub mod headless;
+pub mod input;
+pub mod material;
+pub mod obstacle;
+pub mod snapshot;
+pub mod soft_body;
+pub mod stability;
+pub mod timestep;
+pub mod verlet_object;
+pub mod world;
+
+pub use cgmath::Vector2 as Vec2;
+pub use constraint::{AreaConstraint, DistanceConstraint, LinkKind};
+pub use container::{Boundary, Container};
+pub use diagnostics::Diagnostics;
+pub use emitter::{Emitter, EmitterShape};
+pub use grid::Broadphase;
+pub use material::Material;
+pub use obstacle::Obstacle;
+pub use stability::{ExplosionGuard, Recovery, SubstepRange};
+pub use verlet_object::{Solver, VerletObject};
+pub use world::World;
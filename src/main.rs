@@ -1,190 +1,656 @@
-mod verlet_object;
-
-use crate::verlet_object::*;
-use cgmath::{InnerSpace, Vector2 as Vec2};
 use clap::Parser;
-use rand::Rng;
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use std::path::PathBuf;
+use verlet_integration::config::Config;
+use verlet_integration::diagnostics::CsvWriter;
+use verlet_integration::input::{Controls, InputRecording};
+use verlet_integration::{
+    headless, snapshot, Boundary, Broadphase, Diagnostics, Recovery, SubstepRange, World,
+};
+
+#[cfg(feature = "gui")]
 use raylib::prelude::*;
+#[cfg(feature = "gui")]
+use verlet_integration::input::{FrameInput, RecordingWriter};
+#[cfg(feature = "gui")]
+use verlet_integration::{Container, EmitterShape, Obstacle};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 struct Args {
-    /// Particle Size
-    #[arg(short, long, default_value_t = 10)]
-    particle_size: i32,
+    /// Scene file; flags given on the command line override its values
+    #[arg(long)]
+    config: Option<PathBuf>,
 
-    /// Motion dampening
-    #[arg(short, long, default_value_t = 10)]
-    motion_dampening: i32,
+    /// Particle Size [default: 10]
+    #[arg(short, long)]
+    particle_size: Option<i32>,
 
-    /// Total particles
-    #[arg(short, long, default_value_t = 1000)]
-    total: i32,
+    /// Motion dampening [default: 10]
+    #[arg(short, long)]
+    motion_dampening: Option<i32>,
 
-    /// Simulation substeps
-    #[arg(short, long, default_value_t = 8)]
-    substeps: i32,
+    /// Total particles [default: 1000]
+    #[arg(short, long)]
+    total: Option<i32>,
 
-    /// Simulation gravity
-    #[arg(short, long, default_value_t = 1000)]
-    gravity: i32,
+    /// Simulation substeps [default: 8]
+    #[arg(short, long)]
+    substeps: Option<i32>,
 
-    /// Particle cohesion
-    #[arg(short, long, default_value_t = 0.0)]
-    cohesion: f32,
+    /// Pick substeps every frame from particle speed, within MIN..MAX
+    #[arg(long, value_name = "MIN..MAX")]
+    adaptive_substeps: Option<SubstepRange>,
 
-    /// Particle repulsion
-    #[arg(short, long, default_value_t = 0.0)]
-    repulsion: f32,
+    /// Simulation gravity [default: 1000]
+    #[arg(short, long)]
+    gravity: Option<i32>,
 
-    /// Particle Size Variance
-    #[arg(short, long, default_value_t = 0)]
-    variance: i32,
-}
+    /// Particle cohesion [default: 0]
+    #[arg(short, long)]
+    cohesion: Option<f32>,
 
-const WIDTH: i32 = 800;
-const HEIGHT: i32 = 800;
+    /// Particle repulsion [default: 0]
+    #[arg(short, long)]
+    repulsion: Option<f32>,
 
-fn main() {
-    let args = Args::parse();
-    let (mut rl, thread) = raylib::init()
-        .size(WIDTH, HEIGHT)
-        .title("Digital Snowglobe")
-        .resizable()
-        .build();
+    /// Coulomb static friction between particles [default: 0]
+    #[arg(long)]
+    static_friction: Option<f32>,
 
-    let mut playing = true;
-    let particle_size = args.particle_size as f32;
-    let movement_dampening = args.motion_dampening as f32;
-    let total = args.total;
-    let substeps = args.substeps;
-    let gravity = args.gravity as f32;
-    let cohesion = args.cohesion;
-    let repulsion = args.repulsion;
-    let size_variance = args.variance;
-    let mut fall_off = 100.0;
+    /// Coulomb kinetic friction between particles [default: 0]
+    #[arg(long)]
+    kinetic_friction: Option<f32>,
 
-    let mut rng = rand::rng();
+    /// Solve collisions on one thread, in the order older versions used
+    #[arg(long)]
+    serial_collisions: bool,
 
-    let mut window_pos = unsafe { ffi::GetWindowPosition() };
+    /// Slow particles down to this many units per second [default: no limit]
+    #[arg(long)]
+    max_speed: Option<f32>,
+
+    /// Recover from updates that leave this many times more kinetic energy
+    /// than the last [default: 10 with --recovery, otherwise off]
+    #[arg(long, value_name = "RATIO")]
+    explosion_threshold: Option<f32>,
+
+    /// How to recover from an explosion
+    /// [default: damp with --explosion-threshold, otherwise off]
+    #[arg(long, value_enum)]
+    recovery: Option<Recovery>,
+
+    /// Particle Size Variance, below the particle size [default: 0]
+    #[arg(short, long)]
+    variance: Option<i32>,
+
+    /// Derive particle mass from radius with this density [default: unit mass]
+    #[arg(long)]
+    density: Option<f32>,
+
+    /// Bounciness of the first material, 0 to 1 [default: 0.3]
+    #[arg(long)]
+    restitution: Option<f32>,
+
+    /// Sliding speed lost per wall contact by the first material [default: 0]
+    #[arg(long)]
+    wall_friction: Option<f32>,
+
+    /// Fraction of sliding speed lost per particle contact by the first
+    /// material, at most the overlap [default: 0]
+    #[arg(long)]
+    friction: Option<f32>,
+
+    /// How strongly the first material sticks to walls and neighbours [default: 0]
+    #[arg(long)]
+    adhesion: Option<f32>,
+
+    /// What happens at the edge of the world [default: closed]
+    #[arg(long, value_enum)]
+    boundary: Option<Boundary>,
+
+    /// Window width [default: 800]
+    #[arg(long)]
+    width: Option<i32>,
+
+    /// Window height [default: 800]
+    #[arg(long)]
+    height: Option<i32>,
+
+    /// Random seed; the same seed and arguments reproduce the same run
+    #[arg(long)]
+    seed: Option<u64>,
+
+    /// Resume from a snapshot file instead of spawning a fresh grid
+    #[arg(long)]
+    load: Option<PathBuf>,
+
+    /// Record every frame of input to this file when the app exits
+    #[arg(long)]
+    record: Option<PathBuf>,
+
+    /// Replay input from a recording instead of reading it live
+    #[arg(long)]
+    replay: Option<PathBuf>,
+
+    /// Run without a window and print a summary
+    #[arg(long)]
+    headless: bool,
+
+    /// Time each broadphase over the headless run and report particles/second
+    #[arg(long)]
+    bench: bool,
+
+    /// Frames to simulate in headless mode [default: 600]
+    #[arg(long)]
+    frames: Option<u32>,
+
+    /// Write the headless summary to this file instead of stdout
+    #[arg(long)]
+    output: Option<PathBuf>,
+
+    /// Write energy, momentum and contact diagnostics for every step to this
+    /// CSV file
+    #[arg(long)]
+    diagnostics: Option<PathBuf>,
+}
+
+/// Loads `--config` if given and applies command line overrides on top.
+fn resolve_config(args: &Args) -> Config {
+    let mut config = match &args.config {
+        Some(path) => match Config::load(path) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("failed to load {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        },
+        None => Config::default(),
+    };
+    override_config(args, &mut config);
+
+    if let Err(e) = config.validate() {
+        eprintln!("invalid configuration:\n{}", e);
+        std::process::exit(1);
+    }
+    config
+}
 
-    let mut particles: Vec<VerletObject> = Vec::new();
-    let mut solver = Solver::new(
-        Vec2::new(0.0, gravity),
-        WIDTH,
-        HEIGHT,
+/// Replaces the values in `config` that were given on the command line.
+fn override_config(args: &Args, config: &mut Config) {
+    macro_rules! override_with {
+        ($($field:ident),*) => {
+            $(if let Some(value) = args.$field {
+                config.$field = value;
+            })*
+        };
+    }
+    override_with!(
+        particle_size,
+        motion_dampening,
+        total,
         substeps,
+        gravity,
         cohesion,
         repulsion,
+        static_friction,
+        kinetic_friction,
+        variance,
+        boundary,
+        width,
+        height,
+        frames
     );
+    if args.seed.is_some() {
+        config.seed = args.seed;
+    }
+    if args.adaptive_substeps.is_some() {
+        config.adaptive_substeps = args.adaptive_substeps;
+    }
+    if args.density.is_some() {
+        config.density = args.density;
+    }
+    if args.max_speed.is_some() {
+        config.max_speed = args.max_speed;
+    }
+    if args.explosion_threshold.is_some() || args.recovery.is_some() {
+        let guard = config.explosion_guard.get_or_insert_with(Default::default);
+        if let Some(threshold) = args.explosion_threshold {
+            guard.threshold = threshold;
+        }
+        if let Some(recovery) = args.recovery {
+            guard.recovery = recovery;
+        }
+    }
+    if args.serial_collisions {
+        config.parallel_collisions = false;
+    }
+    if let Some(material) = config.materials.first_mut() {
+        macro_rules! override_material {
+            ($($field:ident),*) => {
+                $(if let Some(value) = args.$field {
+                    material.$field = value;
+                })*
+            };
+        }
+        override_material!(restitution, wall_friction, friction, adhesion);
+    }
+}
+
+fn build_world(args: &Args, config: &Config, rng: &mut StdRng) -> World {
+    if let Some(path) = &args.load {
+        return match snapshot::load(path) {
+            Ok(world) => world,
+            Err(e) => {
+                eprintln!("failed to load {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        };
+    }
+
+    config.build_world(rng)
+}
+
+fn main() {
+    let args = Args::parse();
+    let mut config = resolve_config(&args);
+
+    let replay = args
+        .replay
+        .as_ref()
+        .map(|path| match InputRecording::load(path) {
+            Ok(recording) => recording,
+            Err(e) => {
+                eprintln!("failed to load {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        });
+    // A recording only reproduces the run it came from with the same seed
+    if let Some(recording) = &replay {
+        config.seed = Some(recording.seed);
+    }
+    config.seed.get_or_insert_with(rand::random);
+
+    if args.bench {
+        run_bench(&args, &config);
+        return;
+    }
+
+    if args.headless {
+        run_headless(&args, &config, replay);
+        return;
+    }
+
+    #[cfg(feature = "gui")]
+    run_gui(&args, &config, replay);
+
+    #[cfg(not(feature = "gui"))]
+    {
+        eprintln!("built without the `gui` feature; pass --headless");
+        std::process::exit(1);
+    }
+}
+
+fn seeded_rng(config: &Config) -> StdRng {
+    StdRng::seed_from_u64(config.seed.unwrap_or_default())
+}
+
+/// Opens `--diagnostics` for writing, exiting if it can't be created.
+fn open_diagnostics(args: &Args) -> Option<CsvWriter> {
+    let path = args.diagnostics.as_ref()?;
+    match CsvWriter::create(path) {
+        Ok(writer) => Some(writer),
+        Err(e) => {
+            eprintln!("failed to create {}: {}", path.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Appends a row measured from `world` after `steps` steps of length `dt`.
+fn write_diagnostics(writer: &mut Option<CsvWriter>, world: &World, steps: u32, dt: f32) {
+    if let Some(out) = writer {
+        let row = Diagnostics::measure(world, steps, steps as f32 * dt, dt);
+        if let Err(e) = out.write(&row) {
+            eprintln!("failed to write diagnostics: {}", e);
+            *writer = None;
+        }
+    }
+}
+
+/// Flushes `--diagnostics`, reporting any error.
+fn close_diagnostics(writer: Option<CsvWriter>) {
+    if let Some(mut out) = writer {
+        if let Err(e) = out.flush() {
+            eprintln!("failed to write diagnostics: {}", e);
+        }
+    }
+}
+
+fn run_bench(args: &Args, config: &Config) {
+    let dt = 1.0 / 60.0;
+    println!("seed: {}", config.seed.unwrap_or_default());
 
-    for x in 0..((total as f32).sqrt() as i32) {
-        for y in 0..((total as f32).sqrt() as i32) {
-            let x_pos = (x * particle_size as i32) as f32 * 2.5;
-            let y_pos = (y * particle_size as i32) as f32 * 2.5;
-            particles.push(VerletObject::new(
-                Vec2::new(x_pos + particle_size, y_pos + particle_size),
-                Vec2::new(x_pos + particle_size, y_pos + particle_size),
-                Vec2::new(0.0, 0.0),
-                if size_variance != 0 {
-                    (particle_size + (rng.random_range(-size_variance..size_variance) as f32)).abs()
-                } else {
-                    particle_size
+    let mut baseline = None;
+    for (name, broadphase, parallel) in [
+        ("tree", Broadphase::Tree, false),
+        ("grid", Broadphase::Grid, false),
+        ("grid, parallel", Broadphase::Grid, true),
+    ] {
+        let mut rng = seeded_rng(config);
+        let mut world = build_world(args, config, &mut rng);
+        world.solver.broadphase = broadphase;
+        world.solver.parallel_collisions = parallel;
+        let summary = headless::run(&mut world, config.frames, dt);
+        let rate = summary.particles_per_second();
+        let baseline = *baseline.get_or_insert(rate);
+        println!(
+            "{}: {:.0} particles/s ({:.2}x), {:.3} ms/frame, state_hash {:016x}",
+            name,
+            rate,
+            if baseline > 0.0 { rate / baseline } else { 0.0 },
+            summary.mean_step.as_secs_f64() * 1e3,
+            summary.state_hash
+        );
+    }
+}
+
+fn run_headless(args: &Args, config: &Config, replay: Option<InputRecording>) {
+    let dt = 1.0 / 60.0;
+    let mut rng = seeded_rng(config);
+    let mut world = build_world(args, config, &mut rng);
+    let mut diagnostics = open_diagnostics(args);
+    let mut steps = 0;
+
+    let summary = match &replay {
+        Some(recording) => {
+            let mut controls = Controls::new(config.input_settings());
+            controls.last_save = args.load.clone();
+            let frames = recording.frames.len() as u32;
+            headless::run_with(
+                &mut world,
+                frames,
+                dt,
+                |world, i| {
+                    let input = &recording.frames[i as usize];
+                    if let Err(e) = controls.apply(world, &mut rng, input) {
+                        eprintln!("frame {}: quick save or load failed: {}", i, e);
+                    }
+                    (controls.advance(world, input), controls.timestep.dt)
+                },
+                |world, (taken, dt)| {
+                    if taken > 0 {
+                        steps += taken;
+                        write_diagnostics(&mut diagnostics, world, steps, dt);
+                    }
                 },
-                (255, 255, 255),
-                false,
-            ));
+            )
         }
+        None => headless::run_with(
+            &mut world,
+            config.frames,
+            dt,
+            |world, _| world.step(dt),
+            |world, ()| {
+                steps += 1;
+                write_diagnostics(&mut diagnostics, world, steps, dt);
+            },
+        ),
+    };
+    close_diagnostics(diagnostics);
+    let report = format!("seed: {}\n{}", config.seed.unwrap_or_default(), summary);
+
+    match &args.output {
+        Some(path) => {
+            if let Err(e) = std::fs::write(path, report) {
+                eprintln!("failed to write {}: {}", path.display(), e);
+                std::process::exit(1);
+            }
+        }
+        None => print!("{}", report),
     }
+}
+
+#[cfg(feature = "gui")]
+fn run_gui(args: &Args, config: &Config, replay: Option<InputRecording>) {
+    let (mut rl, thread) = raylib::init()
+        .size(config.width, config.height)
+        .title("Digital Snowglobe")
+        .resizable()
+        .build();
+
+    let mut controls = Controls::new(config.input_settings());
+    controls.last_save = args.load.clone();
+    let mut rng = seeded_rng(config);
+
+    let mut window_pos = unsafe { ffi::GetWindowPosition() };
+
+    let mut world = build_world(args, config, &mut rng);
+
+    let mut replay = replay.map(|recording| recording.frames.into_iter());
+    let mut recording = args.record.as_ref().map(|path| {
+        RecordingWriter::create(path, config.seed.unwrap_or_default()).unwrap_or_else(|e| {
+            eprintln!("failed to create {}: {}", path.display(), e);
+            std::process::exit(1);
+        })
+    });
+    let mut diagnostics = open_diagnostics(args);
+    let mut steps = 0;
 
     while !rl.window_should_close() {
         let new_window_pos = unsafe { ffi::GetWindowPosition() };
-        let mouse_x = rl.get_mouse_x();
-        let mouse_y = rl.get_mouse_y();
-
-        if window_pos.x != new_window_pos.x || window_pos.y != new_window_pos.y {
-            let old = Vec2::new(window_pos.x, window_pos.y);
-            let new = Vec2::new(new_window_pos.x as f32, new_window_pos.y as f32);
-
-            let force_vector = old - new;
-            let n = force_vector / force_vector.magnitude();
-            solver.apply_arbituary_force(&mut particles, n / movement_dampening);
-            window_pos = new_window_pos;
-        }
-
-        if rl.is_mouse_button_down(raylib::consts::MouseButton::MOUSE_BUTTON_RIGHT) {
-            for i in 0..(if fall_off < 0.0 { 10 } else { 1 }) {
-                particles.push(VerletObject::new(
-                    Vec2::new((mouse_x + i) as f32, (mouse_y + i) as f32),
-                    Vec2::new((mouse_x + i) as f32, (mouse_y + i) as f32),
-                    Vec2::new(0.0, 0.0),
-                    if size_variance != 0 && fall_off < 0.0 {
-                        (particle_size + (rng.random_range(-size_variance..size_variance) as f32))
-                            .abs()
-                    } else {
-                        particle_size
-                    },
-                    (255, 255, 255),
-                    fall_off > 0.0,
-                ));
+        let live = FrameInput {
+            window_delta: (
+                window_pos.x - new_window_pos.x,
+                window_pos.y - new_window_pos.y,
+            ),
+            mouse: (rl.get_mouse_x(), rl.get_mouse_y()),
+            left_down: rl.is_mouse_button_down(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT),
+            right_down: rl.is_mouse_button_down(raylib::consts::MouseButton::MOUSE_BUTTON_RIGHT),
+            scroll: rl.get_mouse_wheel_move(),
+            play_down: rl.is_key_down(KeyboardKey::KEY_P),
+            stop_down: rl.is_key_down(KeyboardKey::KEY_S),
+            screen_size: (rl.get_screen_width(), rl.get_screen_height()),
+            cloth_pressed: rl.is_key_pressed(KeyboardKey::KEY_C),
+            soft_body_pressed: rl
+                .is_mouse_button_pressed(raylib::consts::MouseButton::MOUSE_BUTTON_MIDDLE),
+            frame_time: rl.get_frame_time(),
+            slower_pressed: rl.is_key_pressed(KeyboardKey::KEY_MINUS),
+            faster_pressed: rl.is_key_pressed(KeyboardKey::KEY_EQUAL),
+            step_pressed: rl.is_key_pressed(KeyboardKey::KEY_PERIOD),
+            emitter_pressed: rl.is_key_pressed(KeyboardKey::KEY_E),
+            save_json_pressed: rl.is_key_pressed(KeyboardKey::KEY_F5),
+            save_binary_pressed: rl.is_key_pressed(KeyboardKey::KEY_F6),
+            load_pressed: rl.is_key_pressed(KeyboardKey::KEY_F9),
+        };
+        window_pos = new_window_pos;
+
+        // Fall back to live input once the replay runs out
+        let input = replay
+            .as_mut()
+            .and_then(|frames| frames.next())
+            .unwrap_or(live);
+        if let Some(writer) = &mut recording {
+            if let Err(e) = writer.write(&input) {
+                eprintln!("failed to record frame: {}", e);
+                recording = None;
             }
         }
-        if rl.is_mouse_button_down(raylib::consts::MouseButton::MOUSE_BUTTON_LEFT) {
-            solver.apply_point_arbituary_force(
-                &mut particles,
-                Vec2::new(mouse_x as f32, mouse_y as f32),
-                fall_off,
-            );
-        }
-
-        let scroll = rl.get_mouse_wheel_move();
-        fall_off += 5.0 * scroll;
 
-        solver.width = rl.get_screen_width();
-        solver.height = rl.get_screen_height();
+        if let Err(e) = controls.apply(&mut world, &mut rng, &input) {
+            eprintln!("quick save or load failed: {}", e);
+        }
+        let (mouse_x, mouse_y) = input.mouse;
 
         rl.set_target_fps(60);
         rl.set_trace_log(TraceLogLevel::LOG_NONE);
-        if playing {
-            solver.update(
-                &mut particles,
-                1.0 / 60.0 as f32,
-                (particle_size.powf(1.5) + 1.4) as u32,
-            );
+        let taken = controls.advance(&mut world, &input);
+        if taken > 0 {
+            steps += taken;
+            write_diagnostics(&mut diagnostics, &world, steps, controls.timestep.dt);
         }
 
         let mut d = rl.begin_drawing(&thread);
         d.clear_background(Color::BLACK);
 
-        for p in particles.iter() {
+        match world.solver.container {
+            _ if world.solver.boundary != Boundary::Closed => {}
+            Container::Window => {}
+            Container::Circle { x, y, radius } => {
+                d.draw_circle_lines(x as i32, y as i32, radius, Color::DARKGRAY)
+            }
+            Container::Ellipse {
+                x,
+                y,
+                radius_x,
+                radius_y,
+            } => d.draw_ellipse_lines(x as i32, y as i32, radius_x, radius_y, Color::DARKGRAY),
+        }
+
+        for obstacle in world.solver.obstacles.iter() {
+            match obstacle {
+                Obstacle::Segment { a, b } => {
+                    d.draw_line_v(Vector2::new(a.0, a.1), Vector2::new(b.0, b.1), Color::GRAY)
+                }
+                Obstacle::Capsule { a, b, radius } => {
+                    d.draw_line_ex(
+                        Vector2::new(a.0, a.1),
+                        Vector2::new(b.0, b.1),
+                        radius * 2.0,
+                        Color::GRAY,
+                    );
+                    d.draw_circle_v(Vector2::new(a.0, a.1), *radius, Color::GRAY);
+                    d.draw_circle_v(Vector2::new(b.0, b.1), *radius, Color::GRAY);
+                }
+                Obstacle::Polygon { points } => {
+                    for (i, a) in points.iter().enumerate() {
+                        let b = points[(i + 1) % points.len()];
+                        d.draw_line_v(Vector2::new(a.0, a.1), Vector2::new(b.0, b.1), Color::GRAY);
+                    }
+                }
+            }
+        }
+
+        for emitter in world.emitters.iter() {
+            match emitter.shape {
+                EmitterShape::Point { x, y } => {
+                    d.draw_circle_lines(x as i32, y as i32, 4.0, Color::SKYBLUE)
+                }
+                EmitterShape::Segment { a, b } => d.draw_line_v(
+                    Vector2::new(a.0, a.1),
+                    Vector2::new(b.0, b.1),
+                    Color::SKYBLUE,
+                ),
+                EmitterShape::Rect { min, max } => d.draw_rectangle_lines(
+                    min.0 as i32,
+                    min.1 as i32,
+                    (max.0 - min.0) as i32,
+                    (max.1 - min.1) as i32,
+                    Color::SKYBLUE,
+                ),
+            }
+        }
+
+        // Drawn between the last two steps, so motion is smooth at any frame rate
+        let timestep = &controls.timestep;
+        for c in world.constraints.iter() {
+            let a = timestep.position(&world, c.a);
+            let b = timestep.position(&world, c.b);
+            d.draw_line(
+                a.x as i32,
+                a.y as i32,
+                b.x as i32,
+                b.y as i32,
+                Color::LIGHTGRAY,
+            );
+        }
+
+        for (i, p) in world.particles().iter().enumerate() {
             let col = p.col;
+            let pos = timestep.position(&world, i);
             d.draw_circle(
-                p.position_current.x as i32,
-                p.position_current.y as i32,
+                pos.x as i32,
+                pos.y as i32,
                 p.radius,
                 Color::new(col.0, col.1, col.2, 255),
             );
         }
 
+        if world.solver.adaptive_substeps.is_some() {
+            d.draw_text(
+                &format!("substeps: {}", world.solver.substeps),
+                10,
+                34,
+                20,
+                Color::GRAY,
+            );
+        }
+        if timestep.time_scale != 1.0 {
+            d.draw_text(
+                &format!("{}x", timestep.time_scale),
+                10,
+                10,
+                20,
+                Color::GRAY,
+            );
+        }
+
         d.draw_circle_lines(
             mouse_x,
             mouse_y,
-            fall_off,
-            if fall_off > 0.0 {
+            controls.fall_off,
+            if controls.fall_off > 0.0 {
                 Color::GREEN
             } else {
                 Color::RED
             },
         );
+    }
 
-        unsafe {
-            if raylib::ffi::IsKeyDown(KeyboardKey::KEY_P as i32) {
-                playing = true;
-            }
+    close_diagnostics(diagnostics);
+}
 
-            if raylib::ffi::IsKeyDown(KeyboardKey::KEY_S as i32) {
-                playing = false;
-            }
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolve(scene: &str, flags: &[&str]) -> Config {
+        let args = Args::try_parse_from([&["verlet-integration"], flags].concat()).unwrap();
+        let mut config = Config::from_toml_str(scene).unwrap();
+        override_config(&args, &mut config);
+        config
+    }
+
+    #[test]
+    fn flags_override_the_scene_file() {
+        let config = resolve("total = 2000\nsubsteps = 12\n", &["--substeps", "4"]);
+        assert_eq!(config.total, 2000);
+        assert_eq!(config.substeps, 4);
+    }
+
+    #[test]
+    fn missing_flags_keep_the_scene_file() {
+        let scene = "variance = 3\nparallel_collisions = false\nboundary = \"open\"\n";
+        let config = resolve(scene, &[]);
+        assert_eq!(config.variance, 3);
+        assert!(!config.parallel_collisions);
+        assert_eq!(config.boundary, Boundary::Open);
+    }
+
+    #[test]
+    fn material_flags_change_the_first_material() {
+        let scene =
+            "[[materials]]\nname = \"slush\"\nfriction = 0.6\n\n[[materials]]\nname = \"ice\"\n";
+        let config = resolve(scene, &["--friction", "0.1", "--adhesion", "0.2"]);
+        assert_eq!(config.materials[0].friction, 0.1);
+        assert_eq!(config.materials[0].adhesion, 0.2);
+        assert_eq!(config.materials[1].friction, 0.0);
+    }
+
+    #[test]
+    fn overridden_values_are_validated() {
+        let config = resolve("particle_size = 10\n", &["--variance", "12"]);
+        assert!(config.validate().is_err());
     }
 }
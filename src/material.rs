@@ -0,0 +1,66 @@
+use serde::{Deserialize, Serialize};
+
+/// How a particle responds to contact. Particles refer to an entry in
+/// [`Solver::materials`](crate::Solver::materials) by index; contacts
+/// between two particles use the average of both materials.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Material {
+    pub name: String,
+    /// Fraction of the approach speed returned as bounce, at walls and
+    /// between particles.
+    pub restitution: f32,
+    /// Fraction of the sliding speed removed on each wall contact.
+    pub wall_friction: f32,
+    /// Fraction of the relative sliding speed removed on each particle
+    /// contact, capped at the contact's overlap like Coulomb friction is
+    /// capped by the normal force. The solver's `kinetic_friction` loss is
+    /// added on top, and together they never remove more than the sliding
+    /// speed. Contacts held by `static_friction` stop sliding regardless.
+    pub friction: f32,
+    /// Fraction of a small gap closed per substep, pulling the particle onto
+    /// walls and neighbours it is almost touching.
+    pub adhesion: f32,
+    /// Relative share of spawned particles that use this material.
+    pub share: f32,
+}
+
+impl Default for Material {
+    fn default() -> Self {
+        Self {
+            name: "snow".to_string(),
+            restitution: 0.3,
+            wall_friction: 0.0,
+            friction: 0.0,
+            adhesion: 0.0,
+            share: 1.0,
+        }
+    }
+}
+
+impl Material {
+    /// Problems with this material's settings, for config validation.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        for (field, value) in [
+            ("restitution", self.restitution),
+            ("wall_friction", self.wall_friction),
+            ("friction", self.friction),
+            ("adhesion", self.adhesion),
+        ] {
+            if !(0.0..=1.0).contains(&value) {
+                problems.push(format!(
+                    "material `{}`: {} must be between 0 and 1, got {}",
+                    self.name, field, value
+                ));
+            }
+        }
+        if !(self.share >= 0.0 && self.share.is_finite()) {
+            problems.push(format!(
+                "material `{}`: share must not be negative, got {}",
+                self.name, self.share
+            ));
+        }
+        problems
+    }
+}
@@ -0,0 +1,175 @@
+use crate::material::Material;
+use crate::verlet_object::{adhesion_range, resolve_wall_contact, VerletObject};
+use cgmath::{InnerSpace, Vector2 as Vec2};
+use serde::{Deserialize, Serialize};
+
+/// Static collider that particles bounce off like the walls. Points are
+/// `[x, y]` pairs in world coordinates.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Obstacle {
+    Segment {
+        a: (f32, f32),
+        b: (f32, f32),
+    },
+    /// A segment with thickness, rounded at both ends.
+    Capsule {
+        a: (f32, f32),
+        b: (f32, f32),
+        radius: f32,
+    },
+    /// A closed polygon, convex or concave, through `points` in order.
+    Polygon {
+        points: Vec<(f32, f32)>,
+    },
+}
+
+fn v(p: (f32, f32)) -> Vec2<f32> {
+    Vec2::new(p.0, p.1)
+}
+
+fn closest_on_segment(p: Vec2<f32>, a: Vec2<f32>, b: Vec2<f32>) -> Vec2<f32> {
+    let ab = b - a;
+    let len2 = ab.magnitude2();
+    if len2 == 0.0 {
+        return a;
+    }
+    let t = ((p - a).dot(ab) / len2).clamp(0.0, 1.0);
+    a + ab * t
+}
+
+/// Unit normal of the segment, used when a particle sits exactly on it.
+fn segment_normal(a: Vec2<f32>, b: Vec2<f32>) -> Vec2<f32> {
+    let ab = b - a;
+    if ab.magnitude2() == 0.0 {
+        Vec2::new(0.0, -1.0)
+    } else {
+        Vec2::new(-ab.y, ab.x).normalize()
+    }
+}
+
+impl Obstacle {
+    /// Pushes `p` out of the obstacle if it overlaps, responding as `material`.
+    pub fn collide(&self, p: &mut VerletObject, material: &Material) {
+        match self {
+            Obstacle::Segment { a, b } => collide_capsule(p, v(*a), v(*b), 0.0, material),
+            Obstacle::Capsule { a, b, radius } => {
+                collide_capsule(p, v(*a), v(*b), *radius, material)
+            }
+            Obstacle::Polygon { points } => collide_polygon(p, points, material),
+        }
+    }
+
+    /// Axis-aligned `(min, max)` around the obstacle.
+    pub fn bounds(&self) -> (Vec2<f32>, Vec2<f32>) {
+        let (points, pad): (Vec<(f32, f32)>, f32) = match self {
+            Obstacle::Segment { a, b } => (vec![*a, *b], 0.0),
+            Obstacle::Capsule { a, b, radius } => (vec![*a, *b], *radius),
+            Obstacle::Polygon { points } => (points.clone(), 0.0),
+        };
+        let mut min = Vec2::new(f32::INFINITY, f32::INFINITY);
+        let mut max = Vec2::new(f32::NEG_INFINITY, f32::NEG_INFINITY);
+        for (x, y) in points {
+            min.x = min.x.min(x - pad);
+            min.y = min.y.min(y - pad);
+            max.x = max.x.max(x + pad);
+            max.y = max.y.max(y + pad);
+        }
+        (min, max)
+    }
+
+    /// Problems with this obstacle's settings, for config validation.
+    pub fn problems(&self) -> Vec<String> {
+        match self {
+            Obstacle::Segment { .. } => Vec::new(),
+            Obstacle::Capsule { radius, .. } if *radius <= 0.0 => {
+                vec![format!("capsule radius must be positive, got {}", radius)]
+            }
+            Obstacle::Capsule { .. } => Vec::new(),
+            Obstacle::Polygon { points } if points.len() < 3 => vec![format!(
+                "polygon needs at least 3 points, got {}",
+                points.len()
+            )],
+            Obstacle::Polygon { .. } => Vec::new(),
+        }
+    }
+}
+
+/// Gap within which `material` still reacts to a surface.
+fn reach(p: &VerletObject, material: &Material) -> f32 {
+    if material.adhesion > 0.0 {
+        adhesion_range(p)
+    } else {
+        0.0
+    }
+}
+
+fn collide_capsule(
+    p: &mut VerletObject,
+    a: Vec2<f32>,
+    b: Vec2<f32>,
+    radius: f32,
+    material: &Material,
+) {
+    let closest = closest_on_segment(p.position_current, a, b);
+    let axis = p.position_current - closest;
+    let dist = axis.magnitude();
+    let min_dist = p.radius + radius;
+    if dist >= min_dist + reach(p, material) {
+        return;
+    }
+    let n = if dist > 0.0 {
+        axis / dist
+    } else {
+        segment_normal(a, b)
+    };
+    resolve_wall_contact(p, n, min_dist - dist, material);
+}
+
+fn collide_polygon(p: &mut VerletObject, points: &[(f32, f32)], material: &Material) {
+    let n = points.len();
+    if n < 3 {
+        return;
+    }
+    let center = p.position_current;
+
+    // Even-odd ray cast for containment, nearest edge point for the push
+    let mut inside = false;
+    let mut closest = v(points[0]);
+    let mut closest_dist2 = f32::INFINITY;
+    let mut closest_edge = (v(points[0]), v(points[1]));
+    for i in 0..n {
+        let a = v(points[i]);
+        let b = v(points[(i + 1) % n]);
+        if (a.y > center.y) != (b.y > center.y)
+            && center.x < a.x + (center.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+        let c = closest_on_segment(center, a, b);
+        let d2 = (center - c).magnitude2();
+        if d2 < closest_dist2 {
+            closest = c;
+            closest_dist2 = d2;
+            closest_edge = (a, b);
+        }
+    }
+
+    let dist = closest_dist2.sqrt();
+    let axis = center - closest;
+    if inside {
+        let n = if dist > 0.0 {
+            -axis / dist
+        } else {
+            segment_normal(closest_edge.0, closest_edge.1)
+        };
+        resolve_wall_contact(p, n, dist + p.radius, material);
+    } else if dist < p.radius + reach(p, material) {
+        let n = if dist > 0.0 {
+            axis / dist
+        } else {
+            segment_normal(closest_edge.0, closest_edge.1)
+        };
+        resolve_wall_contact(p, n, p.radius - dist, material);
+    }
+}
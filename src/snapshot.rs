@@ -0,0 +1,190 @@
+use crate::constraint::{AreaConstraint, DistanceConstraint};
+use crate::emitter::Emitter;
+use crate::verlet_object::{Solver, VerletObject};
+use crate::world::World;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Bumped whenever the layout of [`Snapshot`] changes incompatibly.
+pub const SNAPSHOT_VERSION: u32 = 18;
+
+/// Leading bytes of the binary format, used to tell it apart from JSON.
+const BINARY_MAGIC: &[u8; 8] = b"SNOWGLB\0";
+
+/// Complete world state: solver parameters and every particle.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub solver: Solver,
+    pub density: Option<f32>,
+    pub particles: Vec<VerletObject>,
+    pub constraints: Vec<DistanceConstraint>,
+    pub area_constraints: Vec<AreaConstraint>,
+    pub emitters: Vec<Emitter>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SnapshotFormat {
+    Json,
+    Binary,
+}
+
+#[derive(Debug)]
+pub enum SnapshotError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Binary(bincode::Error),
+    UnsupportedVersion { found: u32, expected: u32 },
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SnapshotError::Io(e) => write!(f, "{}", e),
+            SnapshotError::Json(e) => write!(f, "invalid JSON snapshot: {}", e),
+            SnapshotError::Binary(e) => write!(f, "invalid binary snapshot: {}", e),
+            SnapshotError::UnsupportedVersion { found, expected } => write!(
+                f,
+                "file version {} is not supported (expected {})",
+                found, expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for SnapshotError {}
+
+impl From<io::Error> for SnapshotError {
+    fn from(e: io::Error) -> Self {
+        SnapshotError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for SnapshotError {
+    fn from(e: serde_json::Error) -> Self {
+        SnapshotError::Json(e)
+    }
+}
+
+impl From<bincode::Error> for SnapshotError {
+    fn from(e: bincode::Error) -> Self {
+        SnapshotError::Binary(e)
+    }
+}
+
+impl SnapshotFormat {
+    /// `.json` files are written as JSON, everything else as binary.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => SnapshotFormat::Json,
+            _ => SnapshotFormat::Binary,
+        }
+    }
+}
+
+impl Snapshot {
+    pub fn capture(world: &World) -> Self {
+        Self {
+            version: SNAPSHOT_VERSION,
+            solver: world.solver.clone(),
+            density: world.density,
+            particles: world.particles.clone(),
+            constraints: world.constraints.clone(),
+            area_constraints: world.area_constraints.clone(),
+            emitters: world.emitters.clone(),
+        }
+    }
+
+    pub fn into_world(self) -> World {
+        let mut world = World::new(self.solver);
+        world.density = self.density;
+        world.particles = self.particles;
+        world.constraints = self.constraints;
+        world.area_constraints = self.area_constraints;
+        world.emitters = self.emitters;
+        world
+    }
+
+    pub fn to_bytes(&self, format: SnapshotFormat) -> Result<Vec<u8>, SnapshotError> {
+        encode(self, self.version, format)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, SnapshotError> {
+        decode(bytes, SNAPSHOT_VERSION)
+    }
+}
+
+/// Serializes a versioned value. Binary output is the magic bytes, the
+/// version as little-endian `u32`, then the bincode body.
+pub(crate) fn encode<T: Serialize>(
+    value: &T,
+    version: u32,
+    format: SnapshotFormat,
+) -> Result<Vec<u8>, SnapshotError> {
+    match format {
+        SnapshotFormat::Json => Ok(serde_json::to_vec_pretty(value)?),
+        SnapshotFormat::Binary => {
+            let mut bytes = BINARY_MAGIC.to_vec();
+            bytes.extend(version.to_le_bytes());
+            bytes.extend(bincode::serialize(value)?);
+            Ok(bytes)
+        }
+    }
+}
+
+/// The bincode body of binary output from [`encode`], after checking its
+/// version, or `None` if `bytes` aren't in the binary format.
+pub(crate) fn binary_body(bytes: &[u8], expected: u32) -> Result<Option<&[u8]>, SnapshotError> {
+    let rest = match bytes.strip_prefix(BINARY_MAGIC) {
+        Some(rest) => rest,
+        None => return Ok(None),
+    };
+    let (version, body) = match rest.split_first_chunk::<4>() {
+        Some((version, body)) => (u32::from_le_bytes(*version), body),
+        None => return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+    };
+    check_version(version, expected)?;
+    Ok(Some(body))
+}
+
+/// Parses either format, detected from the leading bytes. The version is
+/// checked before the body so older files fail with a clear error.
+pub(crate) fn decode<T: DeserializeOwned>(bytes: &[u8], expected: u32) -> Result<T, SnapshotError> {
+    match binary_body(bytes, expected)? {
+        Some(body) => Ok(bincode::deserialize(body)?),
+        None => {
+            let probe: VersionProbe = serde_json::from_slice(bytes)?;
+            check_version(probe.version, expected)?;
+            Ok(serde_json::from_slice(bytes)?)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(crate) struct VersionProbe {
+    pub version: u32,
+}
+
+pub(crate) fn check_version(found: u32, expected: u32) -> Result<(), SnapshotError> {
+    if found == expected {
+        Ok(())
+    } else {
+        Err(SnapshotError::UnsupportedVersion { found, expected })
+    }
+}
+
+/// Writes `world` to `path`, choosing the format from the file extension.
+pub fn save(world: &World, path: &Path) -> Result<(), SnapshotError> {
+    let bytes = Snapshot::capture(world).to_bytes(SnapshotFormat::from_path(path))?;
+    fs::write(path, bytes)?;
+    Ok(())
+}
+
+pub fn load(path: &Path) -> Result<World, SnapshotError> {
+    let bytes = fs::read(path)?;
+    Ok(Snapshot::from_bytes(&bytes)?.into_world())
+}
@@ -0,0 +1,100 @@
+use crate::constraint::{AreaConstraint, DistanceConstraint, LinkKind};
+use crate::verlet_object::VerletObject;
+use crate::world::World;
+use cgmath::Vector2 as Vec2;
+use serde::{Deserialize, Serialize};
+
+/// A closed ring of linked particles that keeps its area, so it squashes and
+/// bounces like a blob. Spawned into a [`World`] with [`SoftBody::spawn`].
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SoftBody {
+    /// Center of the ring.
+    pub x: f32,
+    pub y: f32,
+    /// Radius of the ring itself, not of its particles.
+    pub radius: f32,
+    pub segments: usize,
+    /// Target area as a multiple of the spawned area.
+    pub pressure: f32,
+    /// How hard the area is enforced, in `0.0..=1.0`.
+    pub stiffness: f32,
+}
+
+impl Default for SoftBody {
+    fn default() -> Self {
+        Self {
+            x: 0.0,
+            y: 0.0,
+            radius: 50.0,
+            segments: 24,
+            pressure: 1.0,
+            stiffness: 0.5,
+        }
+    }
+}
+
+impl SoftBody {
+    /// Adds the ring's particles and constraints to `world`. Returns the
+    /// particle indices in order around the ring.
+    pub fn spawn(&self, world: &mut World) -> Vec<usize> {
+        let center = Vec2::new(self.x, self.y);
+        let step = std::f32::consts::TAU / self.segments as f32;
+        let edge = 2.0 * self.radius * (step / 2.0).sin();
+
+        let indices: Vec<usize> = (0..self.segments)
+            .map(|i| {
+                let angle = i as f32 * step;
+                let pos = center + Vec2::new(angle.cos(), angle.sin()) * self.radius;
+                // Neighbours just touch, closing the ring to other particles
+                world.add_particle(VerletObject::new(
+                    pos,
+                    pos,
+                    Vec2::new(0.0, 0.0),
+                    edge / 2.0,
+                    (255, 255, 255),
+                    false,
+                ))
+            })
+            .collect();
+
+        for i in 0..self.segments {
+            let a = indices[i];
+            let b = indices[(i + 1) % self.segments];
+            world
+                .constraints
+                .push(DistanceConstraint::new(a, b, edge, LinkKind::Stick));
+        }
+
+        let mut area = AreaConstraint::new(&world.particles, indices.clone());
+        area.pressure = self.pressure;
+        area.stiffness = self.stiffness;
+        world.area_constraints.push(area);
+
+        indices
+    }
+
+    /// Problems with this body's settings, for config validation.
+    pub fn problems(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        if self.segments < 3 {
+            problems.push(format!(
+                "soft body needs at least 3 segments, got {}",
+                self.segments
+            ));
+        }
+        if self.radius <= 0.0 {
+            problems.push(format!(
+                "soft body radius must be positive, got {}",
+                self.radius
+            ));
+        }
+        if self.pressure <= 0.0 {
+            problems.push(format!(
+                "soft body pressure must be positive, got {}",
+                self.pressure
+            ));
+        }
+        problems
+    }
+}
@@ -0,0 +1,73 @@
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Bounds for adaptive substepping, written `MIN..MAX` on the command line.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SubstepRange {
+    pub min: i32,
+    pub max: i32,
+}
+
+impl FromStr for SubstepRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |part: &str| {
+            part.trim()
+                .parse::<i32>()
+                .map_err(|e| format!("invalid substep count `{}`: {}", part, e))
+        };
+        match s.split_once("..") {
+            Some((min, max)) => Ok(SubstepRange {
+                min: parse(min)?,
+                max: parse(max)?,
+            }),
+            None => Err(format!("expected MIN..MAX, got `{}`", s)),
+        }
+    }
+}
+
+/// How [`Solver::update`](crate::Solver::update) recovers from an update
+/// that blew up.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize, ValueEnum)]
+#[serde(rename_all = "kebab-case")]
+pub enum Recovery {
+    /// Slow every particle down until the energy is back under the limit.
+    #[default]
+    Damp,
+    /// Restore the particles and links as they were after the last good
+    /// update, keeping any added since.
+    Rollback,
+    /// Redo the update with twice the substeps, up to 64, damping if that
+    /// still isn't enough.
+    Substeps,
+}
+
+/// Treats an update as an explosion when it leaves more than `threshold`
+/// times the kinetic energy the last one did, plus what gravity alone could
+/// add, and recovers from it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ExplosionGuard {
+    pub threshold: f32,
+    /// Energy below every particle moving this fast, in units per second,
+    /// never counts as an explosion. A settled pile has next to none, so
+    /// without this floor any push would be many times the last update's.
+    pub min_speed: f32,
+    pub recovery: Recovery,
+}
+
+impl Default for ExplosionGuard {
+    fn default() -> Self {
+        Self {
+            threshold: 10.0,
+            min_speed: 1000.0,
+            recovery: Recovery::Damp,
+        }
+    }
+}
+
+/// Most substeps [`Recovery::Substeps`] retries an update with.
+pub const MAX_RECOVERY_SUBSTEPS: i32 = 64;
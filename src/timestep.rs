@@ -0,0 +1,107 @@
+use crate::world::World;
+use cgmath::{InnerSpace, Vector2 as Vec2};
+
+/// Slowest and fastest [`FixedTimestep::time_scale`] the app allows.
+pub const MIN_TIME_SCALE: f32 = 1.0 / 16.0;
+pub const MAX_TIME_SCALE: f32 = 8.0;
+
+/// Turns variable frame times into a whole number of fixed-length steps, so
+/// the simulation runs at the same speed whatever the frame rate.
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    /// Length of one step in simulated seconds.
+    pub dt: f32,
+    /// Most steps taken for one frame. Time beyond that is dropped, so a slow
+    /// frame slows the simulation down instead of snowballing.
+    pub max_steps: u32,
+    /// Simulated seconds per real second.
+    pub time_scale: f32,
+    accumulator: f32,
+    /// Particle positions before the latest step, for interpolation.
+    previous: Vec<Vec2<f32>>,
+}
+
+impl FixedTimestep {
+    pub fn new(dt: f32, max_steps: u32) -> Self {
+        Self {
+            dt,
+            max_steps,
+            time_scale: 1.0,
+            accumulator: 0.0,
+            previous: Vec::new(),
+        }
+    }
+
+    /// Adds `frame_time` real seconds and returns how many steps are due.
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time.max(0.0) * self.time_scale;
+        let due = (self.accumulator / self.dt).floor() as u32;
+        let steps = due.min(self.max_steps);
+        self.accumulator -= steps as f32 * self.dt;
+        if due > steps {
+            self.accumulator = self.accumulator.min(self.dt);
+        }
+        steps
+    }
+
+    /// Steps `world` for `frame_time` real seconds. Returns the steps taken.
+    pub fn run(&mut self, world: &mut World, frame_time: f32) -> u32 {
+        let steps = self.advance(frame_time);
+        for i in 0..steps {
+            if i + 1 == steps {
+                self.remember(world);
+            }
+            let removed = world.removed_invalid;
+            world.step(self.dt);
+            // Removal moves the last particle into the gap, so indices no
+            // longer match what was remembered
+            if world.removed_invalid != removed {
+                self.previous.clear();
+            }
+        }
+        steps
+    }
+
+    /// Takes exactly one step, e.g. while paused. The result is drawn as is,
+    /// without interpolation.
+    pub fn single_step(&mut self, world: &mut World) {
+        world.step(self.dt);
+        self.reset();
+    }
+
+    /// Forgets the leftover time and the remembered positions, e.g. after
+    /// the world was replaced by a loaded one.
+    pub fn reset(&mut self) {
+        self.previous.clear();
+        self.accumulator = 0.0;
+    }
+
+    fn remember(&mut self, world: &World) {
+        self.previous.clear();
+        self.previous
+            .extend(world.particles().iter().map(|p| p.position_current));
+    }
+
+    /// How far between the last two steps the leftover time reaches, in
+    /// `0.0..=1.0`.
+    pub fn alpha(&self) -> f32 {
+        (self.accumulator / self.dt).clamp(0.0, 1.0)
+    }
+
+    /// Where to draw particle `index`: between its positions before and
+    /// after the latest step, by [`FixedTimestep::alpha`]. Particles that are
+    /// new or wrapped around a periodic edge, and every particle after a step
+    /// that removed some, are drawn where they are.
+    pub fn position(&self, world: &World, index: usize) -> Vec2<f32> {
+        let current = world.particles[index].position_current;
+        let previous = match self.previous.get(index) {
+            Some(previous) => *previous,
+            None => return current,
+        };
+        let jump = (world.solver.width.max(world.solver.height) / 2) as f32;
+        if (current - previous).magnitude2() > jump * jump {
+            return current;
+        }
+        previous + (current - previous) * self.alpha()
+    }
+}
@@ -1,8 +1,16 @@
+use crate::constraint::{pair_mut, unlink_removed, AreaConstraint, DistanceConstraint};
+use crate::container::{Boundary, Container};
+use crate::diagnostics::contacts;
+use crate::grid::{Broadphase, Grid};
+use crate::material::Material;
+use crate::obstacle::Obstacle;
+use crate::stability::{ExplosionGuard, Recovery, SubstepRange, MAX_RECOVERY_SUBSTEPS};
 use cgmath::{InnerSpace, Vector2 as Vec2};
 use rayon::prelude::*;
-use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Clone, Debug, Serialize, Deserialize)]
 pub struct VerletObject {
     pub position_current: Vec2<f32>,
     pub position_old: Vec2<f32>,
@@ -10,15 +18,200 @@ pub struct VerletObject {
     pub radius: f32,
     pub col: (u8, u8, u8),
     pub rigid: bool,
+    /// Only meaningful relative to other particles; rigid particles behave as
+    /// infinitely heavy regardless.
+    pub mass: f32,
+    /// Index into [`Solver::materials`].
+    pub material: usize,
+    /// Keep `col` instead of recolouring the particle by its speed.
+    pub fixed_color: bool,
 }
 
+/// Used when a particle's material index is out of range.
+static FALLBACK_MATERIAL: Material = Material {
+    name: String::new(),
+    restitution: 0.3,
+    wall_friction: 0.0,
+    friction: 0.0,
+    adhesion: 0.0,
+    share: 1.0,
+};
+
+/// Largest distance a particle may move in one substep, as a fraction of the
+/// smallest radius, when substeps are adaptive.
+const MAX_SUBSTEP_TRAVEL: f32 = 0.5;
+
+/// Largest mean overlap of touching particles, as a fraction of the smallest
+/// radius, when substeps are adaptive. Substeps are also the contact solver's
+/// iterations, so a resting pile needs enough of them to stay stacked.
+const MAX_MEAN_PENETRATION: f32 = 0.02;
+
+/// Updates the overlap must stay well under [`MAX_MEAN_PENETRATION`] before
+/// adaptive substeps go down, so a bouncing pile doesn't lose them mid-bounce.
+const CALM_UPDATES: u32 = 60;
+
+/// Radii more than `2^MAX_LEVEL` times the smallest share the top level.
+const MAX_LEVEL: u8 = 15;
+
+/// Neighbouring cells to test against, each pair of cells visited once.
+pub(crate) const NEIGHBOURS: [(i32, i32); 5] = [(0, 0), (0, 1), (1, -1), (1, 0), (1, 1)];
+
+fn material_of<'a>(materials: &'a [Material], p: &VerletObject) -> &'a Material {
+    materials.get(p.material).unwrap_or(&FALLBACK_MATERIAL)
+}
+
+/// Fractions of a pairwise correction taken by `a` and `b`, by inverse mass.
+/// `None` when both are pinned.
+pub(crate) fn mass_ratios(a: &VerletObject, b: &VerletObject) -> Option<(f32, f32)> {
+    let wa = a.inverse_mass();
+    let wb = b.inverse_mass();
+    let w = wa + wb;
+    if w == 0.0 {
+        None
+    } else {
+        Some((wa / w, wb / w))
+    }
+}
+
+/// Rescales every Verlet velocity from substeps of `1 / from` of an update
+/// to `1 / to`, so particles keep their speed per second.
+fn rescale_velocities(particles: &mut [VerletObject], from: i32, to: i32) {
+    if from == to {
+        return;
+    }
+    let scale = from as f32 / to as f32;
+    particles.par_iter_mut().for_each(|p| {
+        p.position_old = p.position_current - p.velocity() * scale;
+    });
+}
+
+/// Direction from `b` to `a` for pushing them apart, where `axis` is
+/// `a - b` and `dist` its length. Coincident particles have no direction
+/// between them, so this falls back to the one between their old positions,
+/// then to the x axis, the same on every run.
+fn contact_normal(axis: Vec2<f32>, dist: f32, a: &VerletObject, b: &VerletObject) -> Vec2<f32> {
+    if dist > 0.0 {
+        return axis / dist;
+    }
+    let old = a.position_old - b.position_old;
+    let old_dist = old.magnitude();
+    if old_dist > 0.0 && old_dist.is_finite() {
+        old / old_dist
+    } else {
+        Vec2::new(1.0, 0.0)
+    }
+}
+
+/// Resolves contact between `p` and a static surface whose normal `n` points
+/// into free space. A positive `depth` is a penetration: `p` is pushed out and
+/// its Verlet velocity reflected with the material's restitution and wall
+/// friction. A negative `depth` is a gap, which adhesion closes part of.
+pub(crate) fn resolve_wall_contact(
+    p: &mut VerletObject,
+    n: Vec2<f32>,
+    depth: f32,
+    material: &Material,
+) {
+    if depth <= 0.0 {
+        p.position_current += n * depth * material.adhesion;
+        return;
+    }
+
+    let v = p.position_current - p.position_old; // Verlet "velocity"
+    let vn = v.dot(n);
+    let v = if vn < 0.0 {
+        let normal = n * vn;
+        (v - normal) * (1.0 - material.wall_friction) - normal * material.restitution
+    } else {
+        v
+    };
+    p.position_current += n * depth;
+    p.position_old = p.position_current - v;
+}
+
+/// Largest gap across which adhesion still pulls `p` in.
+pub(crate) fn adhesion_range(p: &VerletObject) -> f32 {
+    p.radius * 0.25
+}
+
+/// Largest center distance at which cohesion pulls two particles of mean
+/// radius `r` together. This is the cell size the solver used to be given
+/// for particles of radius `r`, whose neighbouring cells were all pulled
+/// together; grid cells are enlarged to cover it while cohesion is on.
+fn cohesion_reach(r: f32) -> f32 {
+    r.powf(1.5) + 1.4
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Solver {
     pub gravity: Vec2<f32>,
+    /// Pulls together particles that don't touch but whose centers are
+    /// closer than `r^1.5 + 1.4`, `r` being their mean radius.
     pub cohesion_multiplier: f32,
     pub repulsion_multiplier: f32,
     pub width: i32,
     pub height: i32,
+    /// Substeps per update. With `adaptive_substeps` set this is rechosen
+    /// every update and holds the count last used.
     pub substeps: i32,
+    /// Pick `substeps` each update so no particle travels more than half the
+    /// smallest radius per substep and touching particles barely overlap,
+    /// within these bounds.
+    pub adaptive_substeps: Option<SubstepRange>,
+    /// Updates in a row the adaptive substeps could have gone down.
+    calm_updates: u32,
+    pub boundary: Boundary,
+    pub container: Container,
+    pub obstacles: Vec<Obstacle>,
+    /// Contact materials, referenced by [`VerletObject::material`].
+    pub materials: Vec<Material>,
+    /// Coulomb friction between touching particles, as multiples of the
+    /// normal correction: contacts sliding slower than `static_friction`
+    /// times it stick, faster ones lose `kinetic_friction` times it.
+    pub static_friction: f32,
+    pub kinetic_friction: f32,
+    pub broadphase: Broadphase,
+    /// Solve grid collisions on all cores. Columns of cells are split between
+    /// threads in a fixed pattern, so results don't depend on thread count or
+    /// scheduling; turning it off replays the single-threaded order instead.
+    pub parallel_collisions: bool,
+    /// Fastest a particle may move, in units per second. Faster particles are
+    /// slowed to it at the end of every substep.
+    pub max_speed: Option<f32>,
+    pub explosion_guard: Option<ExplosionGuard>,
+    /// Updates the explosion guard has had to recover from.
+    #[serde(skip)]
+    pub recoveries: usize,
+    /// Kinetic energy after the last update, for the explosion guard.
+    #[serde(skip)]
+    last_energy: Option<f32>,
+    /// Particles and links after the last good update for
+    /// [`Recovery::Rollback`], or before this one for [`Recovery::Substeps`].
+    #[serde(skip)]
+    saved: Vec<VerletObject>,
+    /// Substep count `saved` was taken with, which its velocities are in.
+    #[serde(skip)]
+    saved_substeps: i32,
+    #[serde(skip)]
+    saved_constraints: Vec<DistanceConstraint>,
+    /// Whether the last update was rolled back.
+    #[serde(skip)]
+    rolled_back: bool,
+    /// Scratch space for [`Broadphase::Grid`], one grid per size level,
+    /// kept between substeps.
+    #[serde(skip)]
+    grids: Vec<Grid>,
+    /// Grid level of each particle.
+    #[serde(skip)]
+    level_of: Vec<u8>,
+    /// Particles copied into grid order for the parallel pass.
+    #[serde(skip)]
+    sorted: Vec<VerletObject>,
+    /// Scratch space for measuring contacts when substeps are adaptive.
+    #[serde(skip)]
+    contact_grid: Grid,
+    #[serde(skip)]
+    contact_levels: Vec<u8>,
 }
 
 fn hue_to_rgb(hue: f32) -> (u8, u8, u8) {
@@ -52,9 +245,26 @@ impl VerletObject {
             radius,
             col,
             rigid,
+            mass: 1.0,
+            material: 0,
+            fixed_color: false,
+        }
+    }
+
+    /// Share of a contact correction this particle takes, zero when pinned.
+    pub fn inverse_mass(&self) -> f32 {
+        if self.rigid || self.mass <= 0.0 {
+            0.0
+        } else {
+            1.0 / self.mass
         }
     }
 
+    /// Sets the mass from the particle's area and `density`.
+    pub fn set_density(&mut self, density: f32) {
+        self.mass = density * std::f32::consts::PI * self.radius * self.radius;
+    }
+
     pub fn update_position(&mut self, dt: f32) {
         if self.rigid {
             return;
@@ -63,13 +273,30 @@ impl VerletObject {
         self.position_old = self.position_current;
         self.position_current = self.position_current + velocity + self.acceleration * dt * dt;
 
-        let hue = hue_to_rgb(240.0 - velocity.magnitude() / 3.0 * 240.0);
-        self.col = hue;
+        if !self.fixed_color {
+            self.col = hue_to_rgb(240.0 - velocity.magnitude() / 3.0 * 240.0);
+        }
 
         self.acceleration.x = 0.0;
         self.acceleration.y = 0.0;
     }
 
+    /// Whether every number in the particle's state is finite, i.e. it has
+    /// not been poisoned by a NaN or an overflow.
+    pub fn is_finite(&self) -> bool {
+        self.position_current.x.is_finite()
+            && self.position_current.y.is_finite()
+            && self.position_old.x.is_finite()
+            && self.position_old.y.is_finite()
+            && self.radius.is_finite()
+            && self.mass.is_finite()
+    }
+
+    /// Displacement over the last substep.
+    pub fn velocity(&self) -> Vec2<f32> {
+        self.position_current - self.position_old
+    }
+
     pub fn accelerate(&mut self, acc: Vec2<f32>) {
         if self.rigid {
             return;
@@ -92,8 +319,31 @@ impl Solver {
             width,
             height,
             substeps,
+            adaptive_substeps: None,
+            calm_updates: 0,
             cohesion_multiplier,
             repulsion_multiplier,
+            boundary: Boundary::Closed,
+            container: Container::Window,
+            obstacles: Vec::new(),
+            materials: vec![Material::default()],
+            static_friction: 0.0,
+            kinetic_friction: 0.0,
+            broadphase: Broadphase::Grid,
+            parallel_collisions: true,
+            max_speed: None,
+            explosion_guard: None,
+            recoveries: 0,
+            last_energy: None,
+            saved: Vec::new(),
+            saved_substeps: 0,
+            saved_constraints: Vec::new(),
+            rolled_back: false,
+            grids: Vec::new(),
+            level_of: Vec::new(),
+            sorted: Vec::new(),
+            contact_grid: Grid::default(),
+            contact_levels: Vec::new(),
         }
     }
 
@@ -115,11 +365,18 @@ impl Solver {
     ) {
         particles.par_iter_mut().for_each(|p| {
             let dist = p.position_current - position;
-            if dist.magnitude() < fall_off.abs() {
+            let length = dist.magnitude();
+            if length < fall_off.abs() {
+                // A particle right on the point has no direction from it; use up
+                let n = if length > 0.0 {
+                    dist / length
+                } else {
+                    Vec2::new(0.0, -1.0)
+                };
                 if fall_off > 0.0 {
-                    p.position_current += dist / dist.magnitude();
+                    p.position_current += n;
                 } else {
-                    p.position_current -= dist / dist.magnitude();
+                    p.position_current -= n;
                 }
             }
         });
@@ -138,86 +395,215 @@ impl Solver {
     }
 
     fn apply_constraint(&mut self, particles: &mut Vec<VerletObject>) {
-        let w = self.width as f32;
-        let h = self.height as f32;
-        let restitution = 0.3;
-        let friction = 1.0; // 1.0 is perfect friction
+        match self.boundary {
+            Boundary::Closed => {}
+            Boundary::Open => return,
+            Boundary::Periodic => return self.apply_periodic_wrap(particles),
+        }
+        match self.container.ellipse() {
+            Some((center, radii)) => self.apply_ellipse_constraint(particles, center, radii),
+            None => self.apply_window_constraint(particles),
+        }
+    }
+
+    /// Moves particles that left the world back in from the opposite side,
+    /// shifting both positions so their velocity is kept.
+    fn apply_periodic_wrap(&mut self, particles: &mut Vec<VerletObject>) {
+        let size = Vec2::new(self.width as f32, self.height as f32);
 
         particles.par_iter_mut().for_each(|p| {
-            let mut pos = p.position_current;
-            let mut old = p.position_old;
-            let mut v = pos - old; // Verlet "velocity"
-
-            // X walls
-            let mut hit_x = false;
-            if pos.x > w - p.radius {
-                pos.x = w - p.radius;
-                v.x = -v.x * restitution;
-                hit_x = true;
+            let wraps = Vec2::new(
+                (p.position_current.x / size.x).floor(),
+                (p.position_current.y / size.y).floor(),
+            );
+            if wraps.x != 0.0 || wraps.y != 0.0 {
+                let shift = Vec2::new(wraps.x * size.x, wraps.y * size.y);
+                p.position_current -= shift;
+                p.position_old -= shift;
             }
-            if pos.x < p.radius {
-                pos.x = p.radius;
-                v.x = -v.x * restitution;
-                hit_x = true;
-            }
-            if hit_x {
-                v.y *= friction;
+        });
+    }
+
+    /// Offset that moves `b` to its closest periodic image to `a`, given
+    /// `axis = a - b`. Zero unless the boundary is periodic.
+    pub(crate) fn periodic_shift(&self, axis: Vec2<f32>) -> Vec2<f32> {
+        if self.boundary != Boundary::Periodic {
+            return Vec2::new(0.0, 0.0);
+        }
+        let (w, h) = (self.width as f32, self.height as f32);
+        let wrap = |d: f32, size: f32| {
+            if d > size / 2.0 {
+                size
+            } else if d < -size / 2.0 {
+                -size
+            } else {
+                0.0
             }
+        };
+        Vec2::new(wrap(axis.x, w), wrap(axis.y, h))
+    }
 
-            // Y walls
-            let mut hit_y = false;
-            if pos.y > h - p.radius {
-                pos.y = h - p.radius;
-                v.y = -v.y * restitution;
-                hit_y = true;
+    fn apply_window_constraint(&mut self, particles: &mut Vec<VerletObject>) {
+        let w = self.width as f32;
+        let h = self.height as f32;
+        let materials = &self.materials;
+
+        particles.par_iter_mut().for_each(|p| {
+            let material = material_of(materials, p);
+            let range = if material.adhesion > 0.0 {
+                adhesion_range(p)
+            } else {
+                0.0
+            };
+
+            // Right, left, bottom, top; depth is how far past the wall the edge is
+            for (n, depth) in [
+                (Vec2::new(-1.0, 0.0), p.position_current.x + p.radius - w),
+                (Vec2::new(1.0, 0.0), p.radius - p.position_current.x),
+                (Vec2::new(0.0, -1.0), p.position_current.y + p.radius - h),
+                (Vec2::new(0.0, 1.0), p.radius - p.position_current.y),
+            ] {
+                if depth > -range {
+                    resolve_wall_contact(p, n, depth, material);
+                }
             }
-            if pos.y < p.radius {
-                pos.y = p.radius;
-                v.y = -v.y * restitution;
-                hit_y = true;
+        });
+    }
+
+    fn apply_ellipse_constraint(
+        &mut self,
+        particles: &mut Vec<VerletObject>,
+        center: Vec2<f32>,
+        radii: Vec2<f32>,
+    ) {
+        let materials = &self.materials;
+
+        particles.par_iter_mut().for_each(|p| {
+            // The particle's center must stay inside the ellipse shrunk by its radius
+            let a = (radii.x - p.radius).max(f32::EPSILON);
+            let b = (radii.y - p.radius).max(f32::EPSILON);
+            let d = p.position_current - center;
+            let k = (d.x / a).powi(2) + (d.y / b).powi(2);
+            if k < 0.25 {
+                return;
             }
-            if hit_y {
-                v.x *= friction;
+
+            // Radial projection is exact for circles and close for ellipses
+            let surface = d / k.sqrt();
+            let n = -Vec2::new(surface.x / (a * a), surface.y / (b * b)).normalize();
+            let depth = (surface - d).dot(n);
+
+            let material = material_of(materials, p);
+            if depth > 0.0 || (material.adhesion > 0.0 && depth > -adhesion_range(p)) {
+                resolve_wall_contact(p, n, depth, material);
             }
+        });
+    }
 
-            // Preserve v_after
-            old = pos - v;
+    fn solve_obstacles(&mut self, particles: &mut Vec<VerletObject>) {
+        if self.obstacles.is_empty() {
+            return;
+        }
+        let bounds: Vec<_> = self.obstacles.iter().map(|o| o.bounds()).collect();
+        let obstacles = &self.obstacles;
+        let materials = &self.materials;
 
-            p.position_current = pos;
-            p.position_old = old;
+        particles.par_iter_mut().for_each(|p| {
+            if p.rigid {
+                return;
+            }
+            let material = material_of(materials, p);
+            let reach = p.radius + adhesion_range(p);
+            for (o, (min, max)) in obstacles.iter().zip(bounds.iter()) {
+                let pos = p.position_current;
+                if pos.x + reach < min.x
+                    || pos.x - reach > max.x
+                    || pos.y + reach < min.y
+                    || pos.y - reach > max.y
+                {
+                    continue;
+                }
+                o.collide(p, material);
+            }
         });
     }
 
-    fn solve_collision(&mut self, a: &mut VerletObject, b: &mut VerletObject) {
+    fn solve_collision(&self, a: &mut VerletObject, b: &mut VerletObject) {
         let axis: Vec2<f32> = a.position_current - b.position_current;
         let dist = axis.magnitude();
+        let ma = material_of(&self.materials, a);
+        let mb = material_of(&self.materials, b);
+        let adhesion = (ma.adhesion + mb.adhesion) * 0.5;
 
         if dist < a.radius + b.radius - self.repulsion_multiplier {
-            let n: Vec2<f32> = axis / dist;
+            let (wa, wb) = match mass_ratios(a, b) {
+                Some(ratios) => ratios,
+                None => return,
+            };
+            let n = contact_normal(axis, dist, a, b);
             let delta = a.radius + b.radius - dist;
-            if !a.rigid {
-                a.position_current += 0.5 * delta * n;
+            let relative = a.velocity() - b.velocity();
+
+            a.position_current += wa * delta * n;
+            b.position_current -= wb * delta * n;
+
+            // Bounce only on impact: resting contacts in a pile are pushed
+            // apart every substep and would otherwise gain energy. The push
+            // already separates at up to `delta`, so only top it up.
+            let vn = relative.dot(n);
+            let restitution = (ma.restitution + mb.restitution) * 0.5;
+            let bounce = -vn * restitution - (vn + delta);
+            let impact = (a.position_old - b.position_old).magnitude() >= a.radius + b.radius;
+            if impact && vn < 0.0 && bounce > 0.0 {
+                a.position_old -= n * bounce * wa;
+                b.position_old += n * bounce * wb;
             }
-            if !b.rigid {
-                b.position_current -= 0.5 * delta * n;
+
+            // Friction cancels sliding, bounded by the push apart like Coulomb
+            // friction is bounded by the normal force
+            let sliding = relative - n * vn;
+            let speed = sliding.magnitude();
+            if speed > 0.0 {
+                let grip = if speed <= self.static_friction * delta {
+                    speed
+                } else {
+                    let material = (ma.friction + mb.friction) * 0.5;
+                    ((speed * material).min(delta) + self.kinetic_friction * delta).min(speed)
+                };
+                let slide = sliding * (grip / speed);
+                a.position_current -= slide * wa;
+                b.position_current += slide * wb;
             }
+        } else if adhesion > 0.0
+            && dist < a.radius + b.radius + adhesion_range(a).min(adhesion_range(b))
+        {
+            let (wa, wb) = match mass_ratios(a, b) {
+                Some(ratios) => ratios,
+                None => return,
+            };
+            let n = contact_normal(axis, dist, a, b);
+            let gap = dist - a.radius - b.radius;
+            a.position_current -= n * gap * adhesion * wa;
+            b.position_current += n * gap * adhesion * wb;
         }
     }
 
-    fn solve_cohesion(&mut self, a: &mut VerletObject, b: &mut VerletObject) {
+    fn solve_cohesion(&self, a: &mut VerletObject, b: &mut VerletObject) {
         let axis: Vec2<f32> = a.position_current - b.position_current;
         let dist = axis.magnitude();
         let e = self.cohesion_multiplier * 1e-4;
 
-        if dist > a.radius + b.radius {
+        let reach = cohesion_reach((a.radius + b.radius) * 0.5);
+        if dist > a.radius + b.radius && dist < reach {
+            let (wa, wb) = match mass_ratios(a, b) {
+                Some(ratios) => ratios,
+                None => return,
+            };
             let n: Vec2<f32> = axis / dist;
             let delta = a.radius + b.radius - dist;
-            if !a.rigid {
-                a.position_current += e * delta * n
-            }
-            if !b.rigid {
-                b.position_current -= e * delta * n
-            }
+            // Equal masses each move by `e * delta`, as before masses existed
+            a.position_current += 2.0 * wa * e * delta * n;
+            b.position_current -= 2.0 * wb * e * delta * n;
         }
     }
 
@@ -225,18 +611,19 @@ impl Solver {
     //     ((x as f32 * 13.8913) / (y as f32 * 0.9381) * 1000000.0) % 255.0
     // }
 
+    // Ordered so collisions resolve in the same sequence on every run.
     fn compute_spatial_map(
         &mut self,
-        particles: &mut Vec<VerletObject>,
-        density: u32,
-    ) -> HashMap<(i32, i32), Vec<i32>> {
-        let mut grid: HashMap<(i32, i32), Vec<i32>> = HashMap::new();
+        particles: &mut [VerletObject],
+        cell_size: f32,
+    ) -> BTreeMap<(i32, i32), Vec<u32>> {
+        let mut grid: BTreeMap<(i32, i32), Vec<u32>> = BTreeMap::new();
 
         for i in 0..particles.len() {
             let p = particles.get_mut(i).unwrap(); // There will always be a particle
 
-            let x = (p.position_current.x / density as f32).floor() as i32;
-            let y = (p.position_current.y / density as f32).floor() as i32;
+            let x = (p.position_current.x / cell_size).floor() as i32;
+            let y = (p.position_current.y / cell_size).floor() as i32;
 
             // Color based on grid
             // p.col = (self.hash_cell(x+1, y+1) as u8, (self.hash_cell(x/2, y*2) + 100.0) as u8, self.hash_cell(y+1, x+1) as u8);
@@ -244,31 +631,109 @@ impl Solver {
             let arr = grid.get_mut(&(x, y));
 
             match arr {
-                Some(v) => v.push(i as i32),
+                Some(v) => v.push(i as u32),
                 None => {
-                    let mut new_arr: Vec<i32> = Vec::new();
-                    new_arr.push(i as i32);
-                    grid.insert((x, y), new_arr);
+                    grid.insert((x, y), vec![i as u32]);
                 }
             }
         }
         grid
     }
 
-    fn find_colllisions(&mut self, particles: &mut Vec<VerletObject>, density: u32) {
-        let grid = self.compute_spatial_map(particles, density);
+    /// Sorts particles into grid levels by radius. Level `k` holds radii in
+    /// `[r_min * 2^k, r_min * 2^(k + 1))`, with cells big enough for any
+    /// contact between two of them; the top level also takes every larger
+    /// radius, so its cells are sized for `r_max`. Returns each level's cell
+    /// size.
+    fn assign_levels(&mut self, particles: &[VerletObject]) -> Vec<f32> {
+        let radii = particles.iter().map(|p| p.radius).filter(|r| *r > 0.0);
+        let r_min = radii.clone().fold(f32::INFINITY, f32::min);
+        let r_max = radii.fold(0.0, f32::max);
+        // Leaves room for adhesion, cohesion and negative repulsion beyond
+        // touching
+        let cohesion = self.cohesion_multiplier != 0.0;
+        let reach = |r: f32| {
+            let contact = 2.5 * r + (-self.repulsion_multiplier).max(0.0);
+            if cohesion {
+                contact.max(cohesion_reach(r))
+            } else {
+                contact
+            }
+        };
+
+        self.level_of.clear();
+        if !(r_min.is_finite() && r_max.is_finite()) {
+            self.level_of.resize(particles.len(), 0);
+            return vec![reach(1.0)];
+        }
+        let level = |r: f32| (r / r_min).log2().floor().clamp(0.0, MAX_LEVEL as f32) as u8;
+        let top = level(r_max);
+        self.level_of.extend(particles.iter().map(|p| {
+            if p.radius > 0.0 {
+                level(p.radius)
+            } else {
+                0
+            }
+        }));
+        (0..top)
+            .map(|k| reach(r_min * 2f32.powi(k as i32 + 1)))
+            .chain([reach(r_max)])
+            .collect()
+    }
+
+    fn find_colllisions(&mut self, particles: &mut [VerletObject]) {
+        let cell_sizes = self.assign_levels(particles);
+        // Only the grid pairs particles across the seam, so a periodic world
+        // uses it whichever broadphase was picked
+        if self.boundary == Boundary::Periodic {
+            self.find_grid_collisions(particles, &cell_sizes);
+            return;
+        }
+        match self.broadphase {
+            Broadphase::Grid => self.find_grid_collisions(particles, &cell_sizes),
+            Broadphase::Tree => self.find_tree_collisions(particles, *cell_sizes.last().unwrap()),
+        }
+    }
+
+    fn find_tree_collisions(&mut self, particles: &mut [VerletObject], cell_size: f32) {
+        let grid = self.compute_spatial_map(particles, cell_size);
 
         for (&(x, y), cell_particles) in &grid {
-            for dx in (-1i32)..=1 {
-                for dy in (-1i32)..=1 {
-                    if dx < 0 || (dx == 0 && dy < 0) {
-                        continue;
-                    }
+            for (dx, dy) in NEIGHBOURS {
+                if let Some(neighbor_cell_particles) = grid.get(&(x + dx, y + dy)) {
+                    self.check_cells_collisions(particles, cell_particles, neighbor_cell_particles);
+                }
+            }
+        }
+    }
+
+    /// Solves each grid level on its own, then every particle against the
+    /// cells of each coarser level around it.
+    fn find_grid_collisions(&mut self, particles: &mut [VerletObject], cell_sizes: &[f32]) {
+        // Taken out so the solve methods can borrow `self`
+        let mut grids = std::mem::take(&mut self.grids);
+        let level_of = std::mem::take(&mut self.level_of);
+        grids.resize_with(cell_sizes.len(), Grid::default);
+        grids.truncate(cell_sizes.len());
 
-                    let nx = x as i32 + dx;
-                    let ny = y as i32 + dy;
-                    if nx >= 0 && ny >= 0 {
-                        if let Some(neighbor_cell_particles) = grid.get(&(nx, ny)) {
+        for (level, (grid, &cell_size)) in grids.iter_mut().zip(cell_sizes).enumerate() {
+            grid.rebuild(
+                particles,
+                &level_of,
+                level as u8,
+                cell_size,
+                (self.width as f32, self.height as f32),
+                self.boundary == Boundary::Periodic,
+            );
+
+            if self.parallel_collisions {
+                self.solve_grid_parallel(particles, grid);
+            } else {
+                for (x, y) in grid.occupied() {
+                    let cell_particles = grid.cell(x, y);
+                    for (dx, dy) in NEIGHBOURS {
+                        let neighbor_cell_particles = grid.cell(x + dx, y + dy);
+                        if !neighbor_cell_particles.is_empty() {
                             self.check_cells_collisions(
                                 particles,
                                 cell_particles,
@@ -279,38 +744,567 @@ impl Solver {
                 }
             }
         }
+
+        // A coarser cell is at least as wide as any contact with its
+        // particles, so the 3x3 block around a particle covers them all
+        for (level, grid) in grids.iter().enumerate().skip(1) {
+            for i in 0..particles.len() {
+                if level_of[i] as usize >= level {
+                    continue;
+                }
+                let (x, y) = grid.cell_of(&particles[i]);
+                for dx in -1..=1 {
+                    for dy in -1..=1 {
+                        for &j in grid.cell(x + dx, y + dy) {
+                            self.solve_pair(particles, i, j as usize);
+                        }
+                    }
+                }
+            }
+        }
+
+        self.grids = grids;
+        self.level_of = level_of;
+    }
+
+    /// Copies the particles into grid order, where every column of cells is
+    /// one contiguous run, and solves them in two passes: first each even
+    /// column against its right-hand neighbour, then each odd one. Within a
+    /// pass no two columns share a particle, so they run on separate threads
+    /// without locking.
+    fn solve_grid_parallel(&mut self, particles: &mut [VerletObject], grid: &Grid) {
+        if grid.columns() == 0 {
+            return;
+        }
+        let mut sorted = std::mem::take(&mut self.sorted);
+        sorted.clear();
+        sorted.extend(
+            grid.entries()
+                .iter()
+                .map(|&i| particles[i as usize].clone()),
+        );
+
+        let columns = grid.columns();
+        for first in 0..2 {
+            // Split off `[column, column + 1]` for every other column
+            let mut tasks = Vec::new();
+            let mut rest = &mut sorted[grid.column_start(first.min(columns))..];
+            let mut offset = grid.column_start(first.min(columns));
+            let mut column = first;
+            while column < columns {
+                let end = grid.column_start((column + 2).min(columns));
+                let (task, tail) = rest.split_at_mut(end - offset);
+                tasks.push((column, offset, task));
+                rest = tail;
+                offset = end;
+                column += 2;
+            }
+
+            let solver = &*self;
+            tasks.into_par_iter().for_each(|(column, offset, task)| {
+                solver.solve_grid_column(task, offset, grid, column);
+            });
+        }
+
+        // The seam between the last column and the first, on one thread
+        if grid.wraps_columns() {
+            let last = columns - 1;
+            for row in 0..grid.rows() {
+                for i in grid.cell_range(last, row) {
+                    for dy in -1..=1 {
+                        for j in grid.wrapped_row_range(0, row + dy) {
+                            self.solve_pair(&mut sorted, i, j);
+                        }
+                    }
+                }
+            }
+        }
+
+        for (&i, p) in grid.entries().iter().zip(sorted.iter()) {
+            particles[i as usize].clone_from(p);
+        }
+        self.sorted = sorted;
+    }
+
+    /// Solves every cell in grid column `column` against its neighbours.
+    /// `particles` holds that column and the next, starting at grid entry
+    /// `offset`.
+    fn solve_grid_column(
+        &self,
+        particles: &mut [VerletObject],
+        offset: usize,
+        grid: &Grid,
+        column: i32,
+    ) {
+        for row in 0..grid.rows() {
+            let cell = grid.cell_range(column, row);
+            if cell.is_empty() {
+                continue;
+            }
+            for (dx, dy) in NEIGHBOURS {
+                // Across the periodic seam is left to the caller
+                if column + dx >= grid.columns() {
+                    continue;
+                }
+                let neighbor = grid.wrapped_row_range(column + dx, row + dy);
+                for i in cell.clone() {
+                    for j in neighbor.clone() {
+                        if i == j {
+                            continue;
+                        }
+                        self.solve_pair(particles, i - offset, j - offset);
+                    }
+                }
+            }
+        }
     }
 
     fn check_cells_collisions(
+        &self,
+        particles: &mut [VerletObject],
+        cell_1: &[u32],
+        cell_2: &[u32],
+    ) {
+        for &p1 in cell_1 {
+            for &p2 in cell_2 {
+                if p1 != p2 {
+                    self.solve_pair(particles, p1 as usize, p2 as usize);
+                }
+            }
+        }
+    }
+
+    /// Solves cohesion and collision between two distinct particles.
+    fn solve_pair(&self, particles: &mut [VerletObject], i: usize, j: usize) {
+        let (a, b) = pair_mut(particles, i, j);
+        // Across a periodic seam, solve against b's nearest image
+        let shift = self.periodic_shift(a.position_current - b.position_current);
+        let wrapped = shift.x != 0.0 || shift.y != 0.0;
+        if wrapped {
+            b.position_current += shift;
+            b.position_old += shift;
+        }
+        self.solve_cohesion(a, b);
+        self.solve_collision(a, b);
+        if wrapped {
+            b.position_current -= shift;
+            b.position_old -= shift;
+        }
+    }
+
+    fn solve_distance_constraints(
         &mut self,
+        particles: &mut [VerletObject],
+        constraints: &mut Vec<DistanceConstraint>,
+    ) {
+        constraints.retain(|c| c.solve(particles));
+    }
+
+    /// Sets `substeps` from how far the fastest particle would travel this
+    /// update and from how deep touching particles overlap, then rescales
+    /// every Verlet velocity to the new substep length.
+    fn choose_substeps(&mut self, particles: &mut [VerletObject], range: SubstepRange, dt: f32) {
+        let previous = self.substeps.max(1);
+        let (max_speed, min_radius) = particles
+            .iter()
+            .filter(|p| !p.rigid && p.radius > 0.0)
+            .fold((0.0f32, f32::INFINITY), |(speed, radius), p| {
+                (speed.max(p.velocity().magnitude()), radius.min(p.radius))
+            });
+        if !min_radius.is_finite() {
+            self.substeps = previous.clamp(range.min, range.max);
+            return;
+        }
+
+        // Distance over the whole update, including what gravity adds
+        let travel = max_speed * previous as f32 + self.gravity.magnitude() * dt * dt;
+        let for_speed = travel / (MAX_SUBSTEP_TRAVEL * min_radius);
+
+        // Overlap left by gravity shrinks with the square of the substeps.
+        // Every change jolts a resting pile, since its overlap is pushed out
+        // at a different speed, so the count holds while the mean overlap is
+        // between a quarter of the target and the target, at most doubles per
+        // update and only halves after a calm stretch
+        let mut grid = std::mem::take(&mut self.contact_grid);
+        let mut levels = std::mem::take(&mut self.contact_levels);
+        let (contacts, depth) = contacts(self, particles, &mut grid, &mut levels);
+        self.contact_grid = grid;
+        self.contact_levels = levels;
+        let penetration = if contacts == 0 {
+            0.0
+        } else {
+            depth / contacts as f32 / (MAX_MEAN_PENETRATION * min_radius)
+        };
+        self.calm_updates = if penetration < 0.25 {
+            self.calm_updates + 1
+        } else {
+            0
+        };
+        let for_depth = if penetration > 1.0 {
+            previous as f32 * penetration.sqrt().min(2.0)
+        } else if contacts == 0 || self.calm_updates >= CALM_UPDATES {
+            self.calm_updates = 0;
+            previous as f32 / 2.0
+        } else {
+            previous as f32
+        };
+
+        let wanted = for_speed.max(for_depth).ceil();
+        let substeps = if wanted.is_finite() {
+            (wanted as i32).clamp(range.min, range.max)
+        } else {
+            range.max
+        };
+
+        rescale_velocities(particles, previous, substeps);
+        self.substeps = substeps;
+    }
+
+    /// Sum of `0.5 * m * v^2` over the finite particles, with `v` in units
+    /// per second after an update of length `dt`.
+    pub fn kinetic_energy(&self, particles: &[VerletObject], dt: f32) -> f32 {
+        let substep_dt = dt / self.substeps.max(1) as f32;
+        particles
+            .iter()
+            .filter(|p| p.is_finite())
+            .map(|p| 0.5 * p.mass * (p.velocity() / substep_dt).magnitude2())
+            .sum()
+    }
+
+    /// Slows every particle faster than `max_speed` down to it.
+    fn clamp_speed(&self, particles: &mut [VerletObject], max_speed: f32, dt: f32) {
+        let limit = max_speed * dt / self.substeps.max(1) as f32;
+        particles.par_iter_mut().for_each(|p| {
+            let v = p.velocity();
+            let speed = v.magnitude();
+            if speed > limit && !p.rigid {
+                p.position_old = p.position_current - v * (limit / speed);
+            }
+        });
+    }
+
+    /// Checks the update just run against the explosion guard and recovers
+    /// if it blew up. `constraints` and `area_constraints` are only needed to
+    /// redo the update with more substeps.
+    fn guard_explosion(
+        &mut self,
+        guard: ExplosionGuard,
         particles: &mut Vec<VerletObject>,
-        cell_1: &Vec<i32>,
-        cell_2: &Vec<i32>,
+        constraints: &mut Vec<DistanceConstraint>,
+        area_constraints: &[AreaConstraint],
+        dt: f32,
     ) {
-        for p1 in cell_1 {
-            for p2 in cell_2 {
-                if p1 == p2 {
-                    continue;
-                };
-                if p1 < p2 {
-                    let (a, b) = particles.split_at_mut(*p2 as usize);
-                    self.solve_cohesion(&mut a[*p1 as usize], &mut b[0]);
-                    self.solve_collision(&mut a[*p1 as usize], &mut b[0]);
-                } else {
-                    let (a, b) = particles.split_at_mut(*p1 as usize);
-                    self.solve_cohesion(&mut b[0], &mut a[*p2 as usize]);
-                    self.solve_collision(&mut b[0], &mut a[*p2 as usize]);
+        // What gravity alone adds in one update to particles starting at
+        // rest, and the floor below which nothing counts
+        let fall = self.gravity.magnitude() * dt;
+        let (rest, floor) = particles.iter().filter(|p| p.is_finite() && !p.rigid).fold(
+            (0.0, 0.0),
+            |(rest, floor), p| {
+                (
+                    rest + 0.5 * p.mass * fall * fall,
+                    floor + 0.5 * p.mass * guard.min_speed * guard.min_speed,
+                )
+            },
+        );
+        let expected = self.last_energy.unwrap_or(0.0) + rest;
+        let limit = (guard.threshold * expected).max(floor);
+        let exploded = |solver: &Self, particles: &[VerletObject]| {
+            solver.kinetic_energy(particles, dt) > limit
+        };
+
+        // With nothing to compare against, any motion would look infinite
+        let blew_up = limit > 0.0 && exploded(self, particles);
+        if blew_up {
+            self.recoveries += 1;
+            match guard.recovery {
+                Recovery::Damp => {}
+                Recovery::Rollback => {
+                    // Rolling back twice in a row would replay the same
+                    // explosion forever
+                    if !self.rolled_back && self.saved.len() == particles.len() {
+                        particles.clone_from(&self.saved);
+                        constraints.clone_from(&self.saved_constraints);
+                        rescale_velocities(particles, self.saved_substeps, self.substeps);
+                        self.rolled_back = true;
+                    }
+                }
+                Recovery::Substeps => {
+                    let substeps = self.substeps;
+                    while exploded(self, particles) && self.substeps < MAX_RECOVERY_SUBSTEPS {
+                        let retry = (self.substeps * 2).min(MAX_RECOVERY_SUBSTEPS);
+                        particles.clone_from(&self.saved);
+                        constraints.clone_from(&self.saved_constraints);
+                        rescale_velocities(particles, self.saved_substeps, retry);
+                        self.substeps = retry;
+                        self.run_substeps(particles, constraints, area_constraints, dt);
+                    }
+                    // Back to the usual count, keeping the retry's speeds
+                    rescale_velocities(particles, self.substeps, substeps);
+                    self.substeps = substeps;
                 }
             }
+            // Whatever is left over is damped back to what was expected, but
+            // never below the floor, so motion is slowed rather than stopped
+            let energy = self.kinetic_energy(particles, dt);
+            if energy > limit {
+                let scale = (expected.max(floor) / energy).sqrt();
+                particles.par_iter_mut().for_each(|p| {
+                    p.position_old = p.position_current - p.velocity() * scale;
+                });
+            }
+        }
+
+        self.last_energy = Some(self.kinetic_energy(particles, dt));
+        if guard.recovery == Recovery::Rollback && !blew_up {
+            self.save(particles, constraints);
+            self.rolled_back = false;
         }
     }
 
-    pub fn update(&mut self, particles: &mut Vec<VerletObject>, dt: f32, density: u32) {
+    fn save(&mut self, particles: &[VerletObject], constraints: &[DistanceConstraint]) {
+        self.saved.clear();
+        self.saved.extend_from_slice(particles);
+        self.saved_constraints.clear();
+        self.saved_constraints.extend_from_slice(constraints);
+        self.saved_substeps = self.substeps;
+    }
+
+    /// Brings the rollback state up to date with particles and links added
+    /// since it was saved, e.g. by an emitter or a new cloth: they are
+    /// appended as they are now.
+    fn save_new(&mut self, particles: &[VerletObject], constraints: &[DistanceConstraint]) {
+        if self.saved.is_empty()
+            || particles.len() < self.saved.len()
+            || constraints.len() < self.saved_constraints.len()
+        {
+            // Nothing saved, or indices no longer line up; start from here
+            self.save(particles, constraints);
+            return;
+        }
+        let start = self.saved.len();
+        self.saved.extend_from_slice(&particles[start..]);
+        rescale_velocities(&mut self.saved[start..], self.substeps, self.saved_substeps);
+        self.saved_constraints
+            .extend_from_slice(&constraints[self.saved_constraints.len()..]);
+    }
+
+    /// Keeps the rollback state in step with [`World::remove_particle`],
+    /// which moves the last particle into the removed one's slot.
+    ///
+    /// [`World::remove_particle`]: crate::world::World::remove_particle
+    pub(crate) fn particle_removed(&mut self, index: usize, count: usize) {
+        if self.saved.len() == count && index < count {
+            self.saved.swap_remove(index);
+            unlink_removed(&mut self.saved_constraints, index, count - 1);
+        } else {
+            // Particles added since can't be told apart any more
+            self.saved.clear();
+        }
+    }
+
+    fn solve_area_constraints(
+        &mut self,
+        particles: &mut [VerletObject],
+        area_constraints: &[AreaConstraint],
+    ) {
+        for c in area_constraints {
+            c.solve(particles);
+        }
+    }
+
+    pub fn update(&mut self, particles: &mut Vec<VerletObject>, dt: f32) {
+        self.update_with_constraints(particles, &mut Vec::new(), &[], dt);
+    }
+
+    /// Like [`Solver::update`], also solving `constraints` and
+    /// `area_constraints` every substep. Links that tear are removed from
+    /// `constraints`.
+    pub fn update_with_constraints(
+        &mut self,
+        particles: &mut Vec<VerletObject>,
+        constraints: &mut Vec<DistanceConstraint>,
+        area_constraints: &[AreaConstraint],
+        dt: f32,
+    ) {
+        let guard = self.explosion_guard;
+        if guard.is_some() && self.last_energy.is_none() {
+            // The starting state is the first good one
+            self.last_energy = Some(self.kinetic_energy(particles, dt));
+            self.save(particles, constraints);
+        } else if matches!(guard, Some(g) if g.recovery == Recovery::Rollback) {
+            self.save_new(particles, constraints);
+        }
+        if let Some(range) = self.adaptive_substeps {
+            self.choose_substeps(particles, range, dt);
+        }
+        if matches!(guard, Some(g) if g.recovery == Recovery::Substeps) {
+            self.save(particles, constraints);
+        }
+        self.run_substeps(particles, constraints, area_constraints, dt);
+        if let Some(guard) = guard {
+            self.guard_explosion(guard, particles, constraints, area_constraints, dt);
+        }
+    }
+
+    fn run_substeps(
+        &mut self,
+        particles: &mut Vec<VerletObject>,
+        constraints: &mut Vec<DistanceConstraint>,
+        area_constraints: &[AreaConstraint],
+        dt: f32,
+    ) {
         for _ in 0..self.substeps {
             self.apply_gravity(particles);
             self.update_positions(particles, dt / (self.substeps as f32));
-            self.find_colllisions(particles, density);
+            self.find_colllisions(particles);
+            self.solve_obstacles(particles);
+            self.solve_area_constraints(particles, area_constraints);
+            self.solve_distance_constraints(particles, constraints);
             self.apply_constraint(particles);
+            if let Some(max_speed) = self.max_speed {
+                self.clamp_speed(particles, max_speed, dt);
+            }
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::{Rng, SeedableRng};
+
+    fn scatter(count: usize, radii: (f32, f32), area: (f32, f32, f32, f32)) -> Vec<VerletObject> {
+        let mut rng = StdRng::seed_from_u64(7);
+        let (x0, y0, x1, y1) = area;
+        (0..count)
+            .map(|_| {
+                let position = Vec2::new(rng.random_range(x0..x1), rng.random_range(y0..y1));
+                let radius = if radii.0 < radii.1 {
+                    rng.random_range(radii.0..radii.1)
+                } else {
+                    radii.0
+                };
+                VerletObject::new(
+                    position,
+                    position,
+                    Vec2::new(0.0, 0.0),
+                    radius,
+                    (255, 255, 255),
+                    false,
+                )
+            })
+            .collect()
+    }
+
+    /// Pairs within `range` of each other that the grids never offer as
+    /// candidates. A pair is found when the coarser particle's grid has it
+    /// in the 3x3 block around the other particle, as `find_grid_collisions`
+    /// relies on.
+    fn missed_pairs(
+        solver: &mut Solver,
+        particles: &[VerletObject],
+        range: impl Fn(&VerletObject, &VerletObject) -> f32,
+    ) -> Vec<(usize, usize)> {
+        let cell_sizes = solver.assign_levels(particles);
+        let grids: Vec<Grid> = cell_sizes
+            .iter()
+            .enumerate()
+            .map(|(level, &cell_size)| {
+                let mut grid = Grid::default();
+                grid.rebuild(
+                    particles,
+                    &solver.level_of,
+                    level as u8,
+                    cell_size,
+                    (solver.width as f32, solver.height as f32),
+                    solver.boundary == Boundary::Periodic,
+                );
+                grid
+            })
+            .collect();
+
+        let mut missed = Vec::new();
+        for i in 0..particles.len() {
+            for j in i + 1..particles.len() {
+                let (a, b) = (&particles[i], &particles[j]);
+                let axis = a.position_current - b.position_current;
+                let axis = axis - solver.periodic_shift(axis);
+                if axis.magnitude() >= range(a, b) {
+                    continue;
+                }
+                let (fine, coarse) = if solver.level_of[i] <= solver.level_of[j] {
+                    (i, j)
+                } else {
+                    (j, i)
+                };
+                let grid = &grids[solver.level_of[coarse] as usize];
+                let (x, y) = grid.cell_of(&particles[fine]);
+                let found = (-1..=1).any(|dx| {
+                    (-1..=1).any(|dy| grid.cell(x + dx, y + dy).contains(&(coarse as u32)))
+                });
+                if !found {
+                    missed.push((i, j));
+                }
+            }
+        }
+        missed
+    }
+
+    fn touching(a: &VerletObject, b: &VerletObject) -> f32 {
+        a.radius + b.radius
+    }
+
+    fn solver() -> Solver {
+        Solver::new(Vec2::new(0.0, 1000.0), 800, 800, 8, 0.0, 0.0)
+    }
+
+    #[test]
+    fn grid_finds_every_contact() {
+        let particles = scatter(1500, (10.0, 10.0), (0.0, 0.0, 800.0, 800.0));
+        assert!(missed_pairs(&mut solver(), &particles, touching).is_empty());
+    }
+
+    #[test]
+    fn grid_finds_every_cohesion_pair() {
+        let particles = scatter(1500, (10.0, 10.0), (0.0, 0.0, 800.0, 800.0));
+        let mut solver = solver();
+        solver.cohesion_multiplier = 5.0;
+        let reach =
+            |a: &VerletObject, b: &VerletObject| cohesion_reach((a.radius + b.radius) * 0.5);
+        assert!(missed_pairs(&mut solver, &particles, reach).is_empty());
+    }
+
+    #[test]
+    fn grid_finds_contacts_between_mixed_sizes() {
+        // Radii from 1 to 40 spread the particles over six levels
+        let particles = scatter(1500, (1.0, 40.0), (0.0, 0.0, 800.0, 800.0));
+        let mut solver = solver();
+        assert!(missed_pairs(&mut solver, &particles, touching).is_empty());
+        assert!(solver.level_of.iter().max() > Some(&3));
+    }
+
+    #[test]
+    fn grid_finds_contacts_left_of_and_above_the_origin() {
+        let particles = scatter(1500, (2.0, 20.0), (-900.0, -700.0, 100.0, 100.0));
+        let mut solver = solver();
+        solver.boundary = Boundary::Open;
+        assert!(missed_pairs(&mut solver, &particles, touching).is_empty());
+    }
+
+    #[test]
+    fn grid_finds_contacts_across_the_periodic_seam() {
+        let mut particles = scatter(1500, (2.0, 20.0), (0.0, 0.0, 800.0, 800.0));
+        // Clusters on both sides of the left/right and top/bottom edges and
+        // in opposite corners, touching only through the seam
+        particles.extend(scatter(2, (10.0, 10.0), (795.0, 300.0, 799.0, 301.0)));
+        particles.extend(scatter(2, (10.0, 10.0), (1.0, 300.0, 5.0, 301.0)));
+        particles.extend(scatter(2, (10.0, 10.0), (500.0, 1.0, 501.0, 5.0)));
+        particles.extend(scatter(2, (10.0, 10.0), (500.0, 795.0, 501.0, 799.0)));
+        particles.extend(scatter(1, (10.0, 10.0), (3.0, 3.0, 4.0, 4.0)));
+        particles.extend(scatter(1, (10.0, 10.0), (796.0, 796.0, 797.0, 797.0)));
+        let mut solver = solver();
+        solver.boundary = Boundary::Periodic;
+        assert!(missed_pairs(&mut solver, &particles, touching).is_empty());
+    }
+}
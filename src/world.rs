@@ -0,0 +1,295 @@
+use crate::constraint::{unlink_removed, AreaConstraint, DistanceConstraint, LinkKind};
+use crate::container::Container;
+use crate::emitter::Emitter;
+use crate::verlet_object::{Solver, VerletObject};
+use cgmath::{InnerSpace, Vector2 as Vec2};
+use rand::Rng;
+
+/// A solver together with the particles it steps.
+pub struct World {
+    pub solver: Solver,
+    pub particles: Vec<VerletObject>,
+    pub constraints: Vec<DistanceConstraint>,
+    pub area_constraints: Vec<AreaConstraint>,
+    /// Particle sources, each adding its particles at the start of a step.
+    pub emitters: Vec<Emitter>,
+    /// When set, particles added to the world get their mass from their
+    /// radius and this density.
+    pub density: Option<f32>,
+    /// Particles removed so far because their state stopped being finite.
+    pub removed_invalid: usize,
+}
+
+impl World {
+    pub fn new(solver: Solver) -> Self {
+        Self {
+            solver,
+            particles: Vec::new(),
+            constraints: Vec::new(),
+            area_constraints: Vec::new(),
+            emitters: Vec::new(),
+            density: None,
+            removed_invalid: 0,
+        }
+    }
+
+    /// Advances the simulation by `dt` seconds.
+    pub fn step(&mut self, dt: f32) {
+        let mut emitters = std::mem::take(&mut self.emitters);
+        for emitter in emitters.iter_mut() {
+            emitter.emit(self, dt);
+        }
+        self.emitters = emitters;
+
+        self.solver.update_with_constraints(
+            &mut self.particles,
+            &mut self.constraints,
+            &self.area_constraints,
+            dt,
+        );
+        self.remove_invalid();
+    }
+
+    /// Removes every particle whose state is no longer finite, so one bad
+    /// particle can't spread NaN through its links. Returns how many went.
+    pub fn remove_invalid(&mut self) -> usize {
+        let mut removed = 0;
+        // Back to front, so the particle moved into a freed slot was checked
+        for i in (0..self.particles.len()).rev() {
+            if !self.particles[i].is_finite() {
+                self.remove_particle(i);
+                removed += 1;
+            }
+        }
+        self.removed_invalid += removed;
+        removed
+    }
+
+    /// Adds a particle and returns its index.
+    pub fn add_particle(&mut self, mut particle: VerletObject) -> usize {
+        if let Some(density) = self.density {
+            particle.set_density(density);
+        }
+        self.particles.push(particle);
+        self.particles.len() - 1
+    }
+
+    /// Picks a material index for a new particle, weighted by each material's
+    /// share. Draws from `rng` only when there is more than one to pick from.
+    pub fn pick_material<R: Rng>(&self, rng: &mut R) -> usize {
+        let materials = &self.solver.materials;
+        let total: f32 = materials.iter().map(|m| m.share).sum();
+        if materials.len() < 2 || total <= 0.0 {
+            return 0;
+        }
+        let mut roll = rng.random_range(0.0..total);
+        for (i, material) in materials.iter().enumerate() {
+            if roll < material.share {
+                return i;
+            }
+            roll -= material.share;
+        }
+        materials.len() - 1
+    }
+
+    /// Removes the particle at `index`, moving the last particle into its slot.
+    /// Constraints attached to it are dropped and those attached to the moved
+    /// particle are re-pointed.
+    pub fn remove_particle(&mut self, index: usize) -> VerletObject {
+        let last = self.particles.len() - 1;
+        self.solver.particle_removed(index, self.particles.len());
+        unlink_removed(&mut self.constraints, index, last);
+        self.area_constraints
+            .retain(|c| !c.indices.contains(&index));
+        for c in self.area_constraints.iter_mut() {
+            for i in c.indices.iter_mut() {
+                if *i == last {
+                    *i = index;
+                }
+            }
+        }
+        self.particles.swap_remove(index)
+    }
+
+    /// Links two particles at their current distance and returns the
+    /// constraint's index.
+    pub fn link(&mut self, a: usize, b: usize, kind: LinkKind) -> usize {
+        let rest_length =
+            (self.particles[a].position_current - self.particles[b].position_current).magnitude();
+        self.constraints
+            .push(DistanceConstraint::new(a, b, rest_length, kind));
+        self.constraints.len() - 1
+    }
+
+    /// Spawns `segments + 1` particles evenly spaced from `start` to `end`,
+    /// each linked to the next. Sticks make a stiff chain, ropes a slack one.
+    /// Returns the particle indices in order; set `rigid` on an end to hang it.
+    pub fn add_chain(
+        &mut self,
+        start: Vec2<f32>,
+        end: Vec2<f32>,
+        segments: usize,
+        radius: f32,
+        kind: LinkKind,
+    ) -> Vec<usize> {
+        let mut indices = Vec::with_capacity(segments + 1);
+        for i in 0..=segments {
+            let t = if segments == 0 {
+                0.0
+            } else {
+                i as f32 / segments as f32
+            };
+            let pos = start + (end - start) * t;
+            indices.push(self.add_particle(VerletObject::new(
+                pos,
+                pos,
+                Vec2::new(0.0, 0.0),
+                radius,
+                (255, 255, 255),
+                false,
+            )));
+        }
+        for pair in indices.windows(2) {
+            self.link(pair[0], pair[1], kind);
+        }
+        indices
+    }
+
+    /// Lays out roughly `total` particles on a square grid from the top-left
+    /// corner. Inside a round container the same spacing fills the container
+    /// from the top instead, so no particle starts outside it; if it is too
+    /// small, fewer particles are spawned.
+    pub fn spawn_grid<R: Rng>(
+        &mut self,
+        total: i32,
+        particle_size: f32,
+        size_variance: i32,
+        rng: &mut R,
+    ) {
+        let side = (total as f32).sqrt() as i32;
+        let mut positions = Vec::with_capacity((side * side) as usize);
+        match self.solver.container {
+            Container::Window => {
+                for x in 0..side {
+                    for y in 0..side {
+                        let x_pos = (x * particle_size as i32) as f32 * 2.5;
+                        let y_pos = (y * particle_size as i32) as f32 * 2.5;
+                        positions.push(Vec2::new(x_pos + particle_size, y_pos + particle_size));
+                    }
+                }
+            }
+            container => {
+                let spacing = particle_size as i32 as f32 * 2.5;
+                let (min, max) = container.bounds();
+                let mut y = min.y + particle_size;
+                while y < max.y && positions.len() < (side * side) as usize {
+                    let mut x = min.x + particle_size;
+                    while x < max.x && positions.len() < (side * side) as usize {
+                        let pos = Vec2::new(x, y);
+                        if container.contains(pos, particle_size) {
+                            positions.push(pos);
+                        }
+                        x += spacing;
+                    }
+                    y += spacing;
+                }
+            }
+        }
+
+        for pos in positions {
+            let mut particle = VerletObject::new(
+                pos,
+                pos,
+                Vec2::new(0.0, 0.0),
+                if size_variance != 0 {
+                    (particle_size + (rng.random_range(-size_variance..size_variance) as f32)).abs()
+                } else {
+                    particle_size
+                },
+                (255, 255, 255),
+                false,
+            );
+            particle.material = self.pick_material(rng);
+            self.add_particle(particle);
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.particles.clear();
+        self.constraints.clear();
+        self.area_constraints.clear();
+    }
+
+    pub fn particles(&self) -> &[VerletObject] {
+        &self.particles
+    }
+
+    pub fn particle_count(&self) -> usize {
+        self.particles.len()
+    }
+
+    pub fn apply_force(&mut self, force_vector: Vec2<f32>) {
+        self.solver
+            .apply_arbituary_force(&mut self.particles, force_vector);
+    }
+
+    pub fn apply_point_force(&mut self, position: Vec2<f32>, fall_off: f32) {
+        self.solver
+            .apply_point_arbituary_force(&mut self.particles, position, fall_off);
+    }
+
+    /// Indices of every particle whose center lies within `radius` of `center`.
+    pub fn particles_in_radius(&self, center: Vec2<f32>, radius: f32) -> Vec<usize> {
+        self.particles
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| (p.position_current - center).magnitude() <= radius)
+            .map(|(i, _)| i)
+            .collect()
+    }
+
+    /// Index of the particle covering `point`, if any.
+    pub fn particle_at(&self, point: Vec2<f32>) -> Option<usize> {
+        self.particles
+            .iter()
+            .position(|p| (p.position_current - point).magnitude() <= p.radius)
+    }
+
+    /// FNV-1a hash over the exact bits of every particle's state. Two runs with
+    /// the same seed and configuration produce the same hash on each frame.
+    pub fn state_hash(&self) -> u64 {
+        const OFFSET: u64 = 0xcbf29ce484222325;
+        const PRIME: u64 = 0x100000001b3;
+
+        let mut hash = OFFSET;
+        let mut write = |bits: u32| {
+            for byte in bits.to_le_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(PRIME);
+            }
+        };
+        for p in self.particles.iter() {
+            write(p.position_current.x.to_bits());
+            write(p.position_current.y.to_bits());
+            write(p.position_old.x.to_bits());
+            write(p.position_old.y.to_bits());
+            write(p.radius.to_bits());
+            write(p.rigid as u32);
+        }
+        hash
+    }
+
+    /// Axis-aligned bounds of all particles including their radii, as `(min, max)`.
+    pub fn bounding_box(&self) -> Option<(Vec2<f32>, Vec2<f32>)> {
+        let first = self.particles.first()?;
+        let mut min = first.position_current;
+        let mut max = first.position_current;
+        for p in self.particles.iter() {
+            min.x = min.x.min(p.position_current.x - p.radius);
+            min.y = min.y.min(p.position_current.y - p.radius);
+            max.x = max.x.max(p.position_current.x + p.radius);
+            max.y = max.y.max(p.position_current.y + p.radius);
+        }
+        Some((min, max))
+    }
+}
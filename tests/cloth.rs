@@ -0,0 +1,101 @@
+use verlet_integration::cloth::{Cloth, ClothPin};
+use verlet_integration::{DistanceConstraint, LinkKind, Solver, Vec2, VerletObject, World};
+
+const DT: f32 = 1.0 / 60.0;
+
+fn world() -> World {
+    World::new(Solver::new(Vec2::new(0.0, 1000.0), 800, 800, 8, 0.0, 0.0))
+}
+
+fn cloth() -> Cloth {
+    Cloth {
+        x: 200.0,
+        y: 100.0,
+        columns: 20,
+        rows: 10,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn spawns_structural_shear_and_bend_links() {
+    let mut world = world();
+    let indices = cloth().spawn(&mut world);
+    assert_eq!(indices.len(), 200);
+    let structural = 19 * 10 + 20 * 9;
+    let shear = 2 * 19 * 9;
+    let bend = 18 * 10 + 20 * 8;
+    assert_eq!(world.constraints.len(), structural + shear + bend);
+}
+
+#[test]
+fn pinned_corners_hold_the_cloth_up() {
+    let mut world = world();
+    let indices = cloth().spawn(&mut world);
+    let links = world.constraints.len();
+    for _ in 0..180 {
+        world.step(DT);
+    }
+
+    // Its own weight doesn't tear it
+    assert_eq!(world.constraints.len(), links);
+    assert_eq!(
+        world.particles[indices[0]].position_current,
+        Vec2::new(200.0, 100.0)
+    );
+    assert_eq!(
+        world.particles[indices[19]].position_current,
+        Vec2::new(390.0, 100.0)
+    );
+    let lowest = world
+        .particles()
+        .iter()
+        .map(|p| p.position_current.y)
+        .fold(0.0, f32::max);
+    assert!(lowest < 400.0, "cloth sagged to {}", lowest);
+}
+
+#[test]
+fn unpinned_cloth_falls() {
+    let mut world = world();
+    let indices = Cloth {
+        pin: ClothPin::None,
+        ..cloth()
+    }
+    .spawn(&mut world);
+    for _ in 0..30 {
+        world.step(DT);
+    }
+    assert!(world.particles[indices[0]].position_current.y > 150.0);
+}
+
+#[test]
+fn links_tear_past_their_ratio() {
+    let position = |x: f32| {
+        let p = Vec2::new(x, 400.0);
+        VerletObject::new(p, p, Vec2::new(0.0, 0.0), 5.0, (255, 255, 255), false)
+    };
+    let mut link = DistanceConstraint::new(0, 1, 10.0, LinkKind::Stick);
+    link.tear_ratio = Some(3.0);
+    assert!(link.solve(&mut [position(400.0), position(429.0)]));
+    assert!(!link.solve(&mut [position(400.0), position(431.0)]));
+}
+
+#[test]
+fn yanked_cloth_tears_only_with_a_tear_ratio() {
+    for (tear_ratio, tears) in [(Some(3.0), true), (None, false)] {
+        let mut world = world();
+        let indices = Cloth {
+            tear_ratio,
+            ..cloth()
+        }
+        .spawn(&mut world);
+        let links = world.constraints.len();
+
+        // Drag a bottom corner far away in one step
+        let corner = indices[indices.len() - 1];
+        world.particles[corner].position_current += Vec2::new(0.0, 300.0);
+        world.step(DT);
+        assert_eq!(world.constraints.len() < links, tears);
+    }
+}
@@ -0,0 +1,52 @@
+use verlet_integration::config::{Config, ConfigError};
+
+fn problems(toml: &str) -> Vec<String> {
+    let config = Config::from_toml_str(toml).unwrap();
+    match config.validate() {
+        Ok(()) => Vec::new(),
+        Err(ConfigError::Invalid(problems)) => problems,
+        Err(e) => panic!("unexpected error: {}", e),
+    }
+}
+
+#[test]
+fn defaults_are_valid() {
+    assert!(Config::default().validate().is_ok());
+}
+
+#[test]
+fn scene_file_values_are_read() {
+    let config = Config::from_toml_str("total = 2000\nsubsteps = 12\nvariance = 3\n").unwrap();
+    assert_eq!(config.total, 2000);
+    assert_eq!(config.substeps, 12);
+    assert_eq!(config.variance, 3);
+    assert_eq!(config.particle_size, Config::default().particle_size);
+}
+
+#[test]
+fn unknown_keys_are_rejected() {
+    assert!(matches!(
+        Config::from_toml_str("substep = 12\n"),
+        Err(ConfigError::Parse(_))
+    ));
+}
+
+#[test]
+fn every_problem_is_reported() {
+    let problems = problems("particle_size = 0\nsubsteps = 0\ntotal = -1\n");
+    assert_eq!(problems.len(), 3, "{:?}", problems);
+}
+
+#[test]
+fn variance_must_stay_below_particle_size() {
+    assert!(problems("particle_size = 10\nvariance = 9\n").is_empty());
+    assert_eq!(problems("particle_size = 10\nvariance = 10\n").len(), 1);
+    assert_eq!(problems("particle_size = 10\nvariance = -1\n").len(), 1);
+}
+
+#[test]
+fn periodic_boundary_needs_the_window() {
+    assert!(problems("boundary = \"periodic\"\n").is_empty());
+    let round = "boundary = \"periodic\"\n[container.circle]\nx = 400\ny = 400\nradius = 380\n";
+    assert_eq!(problems(round).len(), 1);
+}
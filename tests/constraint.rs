@@ -0,0 +1,80 @@
+use verlet_integration::{DistanceConstraint, LinkKind, Solver, Vec2, VerletObject, World};
+
+const DT: f32 = 1.0 / 60.0;
+
+fn particle(x: f32, y: f32, rigid: bool) -> VerletObject {
+    let position = Vec2::new(x, y);
+    VerletObject::new(
+        position,
+        position,
+        Vec2::new(0.0, 0.0),
+        5.0,
+        (255, 255, 255),
+        rigid,
+    )
+}
+
+fn distance(particles: &[VerletObject], c: &DistanceConstraint) -> f32 {
+    let axis = particles[c.a].position_current - particles[c.b].position_current;
+    (axis.x * axis.x + axis.y * axis.y).sqrt()
+}
+
+#[test]
+fn stick_restores_its_rest_length() {
+    for (x, y) in [(420.0, 400.0), (480.0, 400.0), (430.0, 440.0)] {
+        let mut particles = vec![particle(400.0, 400.0, false), particle(x, y, false)];
+        let link = DistanceConstraint::new(0, 1, 50.0, LinkKind::Stick);
+        assert!(link.solve(&mut particles));
+        assert!((distance(&particles, &link) - 50.0).abs() < 1e-3);
+    }
+}
+
+#[test]
+fn rope_only_pulls() {
+    let mut particles = vec![particle(400.0, 400.0, false), particle(420.0, 400.0, false)];
+    let rope = DistanceConstraint::new(0, 1, 50.0, LinkKind::Rope);
+    assert!(rope.solve(&mut particles));
+    assert_eq!(distance(&particles, &rope), 20.0);
+
+    particles[1] = particle(480.0, 400.0, false);
+    assert!(rope.solve(&mut particles));
+    assert!((distance(&particles, &rope) - 50.0).abs() < 1e-3);
+}
+
+#[test]
+fn pinned_end_takes_no_correction() {
+    let mut particles = vec![particle(400.0, 400.0, true), particle(480.0, 400.0, false)];
+    let link = DistanceConstraint::new(0, 1, 50.0, LinkKind::Stick);
+    assert!(link.solve(&mut particles));
+    assert_eq!(particles[0].position_current, Vec2::new(400.0, 400.0));
+    assert!((particles[1].position_current.x - 450.0).abs() < 1e-3);
+}
+
+#[test]
+fn hanging_chain_keeps_its_length() {
+    let mut world = World::new(Solver::new(Vec2::new(0.0, 1000.0), 800, 800, 8, 0.0, 0.0));
+    let chain = world.add_chain(
+        Vec2::new(400.0, 100.0),
+        Vec2::new(600.0, 100.0),
+        10,
+        5.0,
+        LinkKind::Stick,
+    );
+    world.particles[chain[0]].rigid = true;
+    for _ in 0..180 {
+        world.step(DT);
+    }
+
+    assert_eq!(world.constraints.len(), 10);
+    for link in world.constraints.iter() {
+        let stretch = distance(world.particles(), link) / link.rest_length;
+        assert!((stretch - 1.0).abs() < 0.05, "stretched to {}", stretch);
+    }
+    // Fell from level, but never further from the pin than the chain's length
+    let end = world.particles[chain[10]].position_current;
+    let pin = world.particles[chain[0]].position_current;
+    assert_eq!(pin, Vec2::new(400.0, 100.0));
+    assert!(end.y > 150.0, "end at {:?}", end);
+    let reach = ((end.x - pin.x).powi(2) + (end.y - pin.y).powi(2)).sqrt();
+    assert!(reach < 200.0 * 1.05, "end {} from the pin", reach);
+}
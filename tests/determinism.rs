@@ -0,0 +1,40 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use verlet_integration::config::Config;
+use verlet_integration::World;
+
+const DT: f32 = 1.0 / 60.0;
+
+fn world(seed: u64) -> World {
+    let config = Config {
+        total: 300,
+        variance: 4,
+        ..Config::default()
+    };
+    config.build_world(&mut StdRng::seed_from_u64(seed))
+}
+
+fn hash_after(mut world: World, frames: u32) -> u64 {
+    for _ in 0..frames {
+        world.step(DT);
+    }
+    world.state_hash()
+}
+
+#[test]
+fn same_seed_gives_same_hash() {
+    assert_eq!(hash_after(world(1), 120), hash_after(world(1), 120));
+    assert_ne!(hash_after(world(1), 120), hash_after(world(2), 120));
+}
+
+#[test]
+fn hash_does_not_depend_on_thread_count() {
+    let hash_with = |threads| {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build()
+            .unwrap()
+            .install(|| hash_after(world(1), 120))
+    };
+    assert_eq!(hash_with(1), hash_with(16));
+}
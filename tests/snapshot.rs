@@ -0,0 +1,63 @@
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use verlet_integration::config::Config;
+use verlet_integration::snapshot::{Snapshot, SnapshotFormat};
+use verlet_integration::{Emitter, EmitterShape, World};
+
+const DT: f32 = 1.0 / 60.0;
+
+/// Steps `live` a while, saves and reloads it in `format`, then checks the
+/// copy carries on exactly as the original does.
+fn round_trip(mut live: World, format: SnapshotFormat) {
+    for _ in 0..60 {
+        live.step(DT);
+    }
+    let bytes = Snapshot::capture(&live).to_bytes(format).unwrap();
+    let mut loaded = Snapshot::from_bytes(&bytes).unwrap().into_world();
+    assert_eq!(loaded.state_hash(), live.state_hash());
+
+    for _ in 0..120 {
+        live.step(DT);
+        loaded.step(DT);
+    }
+    assert_eq!(loaded.particle_count(), live.particle_count());
+    assert_eq!(loaded.state_hash(), live.state_hash());
+}
+
+fn pile() -> World {
+    let config = Config {
+        total: 200,
+        variance: 4,
+        ..Config::default()
+    };
+    config.build_world(&mut StdRng::seed_from_u64(1))
+}
+
+#[test]
+fn json_round_trip() {
+    round_trip(pile(), SnapshotFormat::Json);
+}
+
+#[test]
+fn binary_round_trip() {
+    round_trip(pile(), SnapshotFormat::Binary);
+}
+
+#[test]
+fn emitters_carry_on_after_a_round_trip() {
+    let mut emitter = Emitter::default();
+    emitter.shape = EmitterShape::Segment {
+        a: (100.0, 50.0),
+        b: (700.0, 50.0),
+    };
+    // A fractional rate leaves part of a particle owed between frames
+    emitter.rate = 45.5;
+    emitter.max_particles = Some(100);
+    let config = Config {
+        total: 0,
+        emitters: vec![emitter],
+        ..Config::default()
+    };
+    let world = config.build_world(&mut StdRng::seed_from_u64(1));
+    round_trip(world, SnapshotFormat::Json);
+}
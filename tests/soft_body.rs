@@ -0,0 +1,79 @@
+use verlet_integration::soft_body::SoftBody;
+use verlet_integration::{Solver, Vec2, World};
+
+const DT: f32 = 1.0 / 60.0;
+
+fn area(world: &World, indices: &[usize]) -> f32 {
+    let mut twice = 0.0;
+    for (i, &a) in indices.iter().enumerate() {
+        let p = world.particles[a].position_current;
+        let q = world.particles[indices[(i + 1) % indices.len()]].position_current;
+        twice += p.x * q.y - q.x * p.y;
+    }
+    (twice / 2.0).abs()
+}
+
+/// Drops `body` onto the floor and returns its area after it settles,
+/// relative to the area it spawned with.
+fn settled_area(body: SoftBody) -> f32 {
+    let mut world = World::new(Solver::new(Vec2::new(0.0, 1000.0), 800, 800, 8, 0.0, 0.0));
+    let indices = body.spawn(&mut world);
+    let spawned = area(&world, &indices);
+    for _ in 0..240 {
+        world.step(DT);
+    }
+    assert!(world.particles().iter().all(|p| p.is_finite()));
+    area(&world, &indices) / spawned
+}
+
+fn body() -> SoftBody {
+    SoftBody {
+        x: 400.0,
+        y: 600.0,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn resting_body_keeps_its_area() {
+    let ratio = settled_area(body());
+    assert!(
+        (ratio - 1.0).abs() < 0.1,
+        "area is {} of the spawned",
+        ratio
+    );
+}
+
+#[test]
+fn pressure_inflates_the_body() {
+    let ratio = settled_area(SoftBody {
+        pressure: 1.3,
+        ..body()
+    });
+    assert!(ratio > 1.0, "area is {} of the spawned", ratio);
+}
+
+#[test]
+fn area_constraint_resists_squashing() {
+    let squashed = |stiffness: f32| {
+        let mut world = World::new(Solver::new(Vec2::new(0.0, 0.0), 800, 800, 8, 0.0, 0.0));
+        let indices = SoftBody {
+            stiffness,
+            ..body()
+        }
+        .spawn(&mut world);
+        let spawned = area(&world, &indices);
+        // Flatten the ring vertically and let it recover
+        for &i in indices.iter() {
+            let p = &mut world.particles[i];
+            p.position_current.y = 600.0 + (p.position_current.y - 600.0) * 0.5;
+            p.position_old = p.position_current;
+        }
+        for _ in 0..60 {
+            world.step(DT);
+        }
+        area(&world, &indices) / spawned
+    };
+    assert!(squashed(1.0) > squashed(0.0) + 0.1);
+    assert!(squashed(1.0) > 0.9, "recovered to {}", squashed(1.0));
+}
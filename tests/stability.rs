@@ -0,0 +1,105 @@
+use verlet_integration::cloth::Cloth;
+use verlet_integration::{ExplosionGuard, Recovery, Solver, Vec2, VerletObject, World};
+
+const DT: f32 = 1.0 / 60.0;
+
+fn particle(x: f32, y: f32) -> VerletObject {
+    let position = Vec2::new(x, y);
+    VerletObject::new(
+        position,
+        position,
+        Vec2::new(0.0, 0.0),
+        10.0,
+        (255, 255, 255),
+        false,
+    )
+}
+
+fn solver() -> Solver {
+    Solver::new(Vec2::new(0.0, 1000.0), 800, 800, 8, 0.0, 0.0)
+}
+
+#[test]
+fn coincident_particles_stay_finite() {
+    let mut world = World::new(solver());
+    for _ in 0..20 {
+        world.add_particle(particle(400.0, 400.0));
+    }
+    for _ in 0..60 {
+        world.step(DT);
+    }
+    assert_eq!(world.removed_invalid, 0);
+    assert_eq!(world.particle_count(), 20);
+    assert!(world.particles().iter().all(|p| p.is_finite()));
+}
+
+/// A crowd of particles packed far tighter than they fit, which flies apart
+/// on the first update.
+fn crowded(guard: Option<ExplosionGuard>) -> World {
+    let mut solver = solver();
+    solver.explosion_guard = guard;
+    let mut world = World::new(solver);
+    for i in 0..100 {
+        world.add_particle(particle(400.0 + (i % 10) as f32, 400.0 + (i / 10) as f32));
+    }
+    world
+}
+
+#[test]
+fn every_recovery_calms_an_explosion() {
+    let mut unguarded = crowded(None);
+    unguarded.step(DT);
+    let wild = unguarded.solver.kinetic_energy(unguarded.particles(), DT);
+
+    for recovery in [Recovery::Damp, Recovery::Rollback, Recovery::Substeps] {
+        let mut world = crowded(Some(ExplosionGuard {
+            threshold: 2.0,
+            min_speed: 10.0,
+            recovery,
+        }));
+        world.step(DT);
+        let energy = world.solver.kinetic_energy(world.particles(), DT);
+        assert!(
+            world.solver.recoveries > 0,
+            "{:?} never recovered",
+            recovery
+        );
+        assert!(
+            energy < wild / 10.0,
+            "{:?} left {} of {}",
+            recovery,
+            energy,
+            wild
+        );
+    }
+}
+
+#[test]
+fn rollback_restores_torn_links() {
+    let mut world = World::new(solver());
+    world.solver.explosion_guard = Some(ExplosionGuard {
+        recovery: Recovery::Rollback,
+        ..ExplosionGuard::default()
+    });
+    let cloth = Cloth {
+        x: 300.0,
+        y: 100.0,
+        columns: 10,
+        rows: 10,
+        ..Cloth::default()
+    };
+    cloth.spawn(&mut world);
+    for _ in 0..10 {
+        world.step(DT);
+    }
+    let links = world.constraints.len();
+    let before = world.particles[55].position_current;
+
+    // Yank one particle hard enough to tear its links
+    world.particles[55].position_old = before - Vec2::new(5000.0, 0.0);
+    world.step(DT);
+
+    assert_eq!(world.solver.recoveries, 1);
+    assert_eq!(world.constraints.len(), links);
+    assert_eq!(world.particles[55].position_current, before);
+}
@@ -0,0 +1,60 @@
+use verlet_integration::timestep::FixedTimestep;
+use verlet_integration::{Solver, Vec2, VerletObject, World};
+
+const DT: f32 = 1.0 / 60.0;
+
+#[test]
+fn leftover_time_carries_to_the_next_frame() {
+    let mut timestep = FixedTimestep::new(DT, 5);
+    assert_eq!(timestep.advance(DT * 0.6), 0);
+    assert!((timestep.alpha() - 0.6).abs() < 1e-4);
+    assert_eq!(timestep.advance(DT * 0.6), 1);
+    assert!((timestep.alpha() - 0.2).abs() < 1e-4);
+
+    // Frames of any length add up to the same number of steps
+    let steps: u32 = (0..120).map(|_| timestep.advance(DT * 1.5)).sum();
+    assert_eq!(steps, 180);
+}
+
+#[test]
+fn slow_frames_are_capped() {
+    let mut timestep = FixedTimestep::new(DT, 5);
+    assert_eq!(timestep.advance(1.0), 5);
+    // Dropped time doesn't pile up into the next frames
+    assert!(timestep.alpha() <= 1.0);
+    assert_eq!(timestep.advance(0.0), 1);
+    assert_eq!(timestep.advance(0.0), 0);
+}
+
+#[test]
+fn time_scale_changes_the_steps_taken() {
+    let mut timestep = FixedTimestep::new(DT, 5);
+    timestep.time_scale = 0.5;
+    let steps: u32 = (0..60).map(|_| timestep.advance(DT)).sum();
+    assert_eq!(steps, 30);
+}
+
+#[test]
+fn reset_forgets_leftover_time_and_interpolation() {
+    let mut world = World::new(Solver::new(Vec2::new(0.0, 1000.0), 800, 800, 8, 0.0, 0.0));
+    let position = Vec2::new(400.0, 100.0);
+    world.add_particle(VerletObject::new(
+        position,
+        position,
+        Vec2::new(0.0, 0.0),
+        10.0,
+        (255, 255, 255),
+        false,
+    ));
+    let mut timestep = FixedTimestep::new(DT, 5);
+    timestep.run(&mut world, DT * 2.5);
+    let current = world.particles[0].position_current;
+    assert!(timestep.alpha() > 0.0);
+    assert_ne!(timestep.position(&world, 0), current);
+
+    // As after loading a snapshot into `world`
+    timestep.reset();
+    assert_eq!(timestep.alpha(), 0.0);
+    assert_eq!(timestep.position(&world, 0), current);
+    assert_eq!(timestep.advance(DT * 0.9), 0);
+}